@@ -3,7 +3,10 @@
 
 pub const USER_STACK_SIZE: usize = 4096 * 2;
 pub const KERNEL_STACK_SIZE: usize = 4096 * 2;
-pub const KERNEL_HEAP_SIZE: usize = 0x20_0000;
+/// Each user page costs heap for its `Page`, its `data_frames` entry and its
+/// slot in the swap clock. With swap, a task can touch more pages than there
+/// are frames: the 34816 pages of `swaptest_simple` take about 4MiB.
+pub const KERNEL_HEAP_SIZE: usize = 0x80_0000;
 
 pub const PAGE_SIZE: usize = 0x1000;
 pub const PAGE_SIZE_BITS: usize = 0xc;
@@ -11,4 +14,26 @@ pub const PAGE_SIZE_BITS: usize = 0xc;
 pub const TRAMPOLINE: usize = usize::MAX - PAGE_SIZE + 1;
 pub const TRAP_CONTEXT: usize = TRAMPOLINE - PAGE_SIZE;
 
-pub use crate::board::{CLOCK_FREQ, MEMORY_END, MMIO};
+/// harts the kernel can run on, others are parked in `entry.asm`
+pub const MAX_HARTS: usize = 4;
+/// stack each hart runs `rust_main` and its idle loop on, reserved in `entry.asm`
+pub const BOOT_STACK_SIZE: usize = 4096 * 16;
+
+/// physical address `pa` is mapped at `pa + PHYS_VIRT_OFFSET` in kernel space,
+/// the same offset is used in `linker.ld` and `entry.asm`
+pub const PHYS_VIRT_OFFSET: usize = 0xffff_ffc0_0000_0000;
+
+/// file descriptors of a task are below this, like `RLIMIT_NOFILE`
+pub const MAX_FD: usize = 1024;
+
+/// lowest address `mmap` picks when the caller does not ask for one
+pub const MMAP_BASE: usize = 0x10_0000_0000;
+/// end of the lower half of the Sv39 address space, where user mappings live
+pub const USER_SPACE_END: usize = 0x40_0000_0000;
+
+/// first block of the swap area, right after the 16MiB easy-fs image
+pub const SWAP_START_BLOCK: usize = 16 * 2048;
+/// 64MiB of swap
+pub const SWAP_PAGES: usize = 16384;
+
+pub use crate::board::{CLOCK_FREQ, MEMORY_END, MMIO, RTC_BASE};
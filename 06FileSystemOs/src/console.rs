@@ -1,5 +1,6 @@
 //! SBI console driver, for text output
 use crate::sbi::console_putchar;
+use crate::sync::SpinLock;
 use core::fmt::{self, Write};
 
 struct Stdout;
@@ -13,8 +14,11 @@ impl Write for Stdout {
     }
 }
 
+/// held while printing, so that lines printed by different harts do not interleave
+static STDOUT: SpinLock<Stdout> = SpinLock::new(Stdout);
+
 pub fn print(args: fmt::Arguments) {
-    Stdout.write_fmt(args).unwrap();
+    STDOUT.exclusive_access().write_fmt(args).unwrap();
 }
 
 #[macro_export]
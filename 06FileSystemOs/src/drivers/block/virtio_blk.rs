@@ -1,9 +1,9 @@
 use super::BlockDevice;
 use crate::mm::{
-    frame_alloc, frame_dealloc, kernel_token, FrameTracker, PageTable, PhysAddr, PhysPageNum,
-    StepByOne, VirtAddr,
+    frame_alloc_contiguous, frame_dealloc, kernel_token, FrameTracker, PageTable, PhysAddr,
+    PhysPageNum, StepByOne, VirtAddr,
 };
-use crate::sync::UPSafeCell;
+use crate::sync::SpinLock;
 use alloc::vec::Vec;
 use lazy_static::*;
 use virtio_drivers::{Hal, VirtIOBlk, VirtIOHeader};
@@ -11,10 +11,10 @@ use virtio_drivers::{Hal, VirtIOBlk, VirtIOHeader};
 #[allow(unused)]
 const VIRTIO0: usize = 0x10001000;
 
-pub struct VirtIOBlock(UPSafeCell<VirtIOBlk<'static, VirtioHal>>);
+pub struct VirtIOBlock(SpinLock<VirtIOBlk<'static, VirtioHal>>);
 
 lazy_static! {
-    static ref QUEUE_FRAMES: UPSafeCell<Vec<FrameTracker>> = unsafe { UPSafeCell::new(Vec::new()) };
+    static ref QUEUE_FRAMES: SpinLock<Vec<FrameTracker>> = SpinLock::new(Vec::new());
 }
 
 impl BlockDevice for VirtIOBlock {
@@ -36,8 +36,11 @@ impl VirtIOBlock {
     #[allow(unused)]
     pub fn new() -> Self {
         unsafe {
-            Self(UPSafeCell::new(
-                VirtIOBlk::<VirtioHal>::new(&mut *(VIRTIO0 as *mut VirtIOHeader)).unwrap(),
+            Self(SpinLock::new(
+                VirtIOBlk::<VirtioHal>::new(
+                    &mut *(VirtioHal::phys_to_virt(VIRTIO0) as *mut VirtIOHeader),
+                )
+                .unwrap(),
             ))
         }
     }
@@ -47,16 +50,9 @@ pub struct VirtioHal;
 
 impl Hal for VirtioHal {
     fn dma_alloc(pages: usize) -> usize {
-        let mut ppn_base = PhysPageNum(0);
-        for i in 0..pages {
-            let frame = frame_alloc().unwrap();
-            if i == 0 {
-                ppn_base = frame.ppn;
-            }
-            assert_eq!(frame.ppn.0, ppn_base.0 + i);
-            QUEUE_FRAMES.exclusive_access().push(frame);
-        }
-        let pa: PhysAddr = ppn_base.into();
+        let frames = frame_alloc_contiguous(pages, 1).unwrap();
+        let pa: PhysAddr = frames[0].ppn.into();
+        QUEUE_FRAMES.exclusive_access().extend(frames);
         pa.0
     }
 
@@ -71,7 +67,7 @@ impl Hal for VirtioHal {
     }
 
     fn phys_to_virt(addr: usize) -> usize {
-        addr
+        PhysAddr::from(addr).kernel_va().into()
     }
 
     fn virt_to_phys(vaddr: usize) -> usize {
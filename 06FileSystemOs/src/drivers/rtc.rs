@@ -0,0 +1,19 @@
+//! Goldfish RTC of the qemu virt machine, which counts wall-clock time
+use crate::config::RTC_BASE;
+use crate::mm::PhysAddr;
+use core::ptr::read_volatile;
+
+/// register with the low 32 bits of the time, reading it latches the high bits
+const TIME_LOW: usize = 0x00;
+/// register with the high 32 bits of the time latched by reading `TIME_LOW`
+const TIME_HIGH: usize = 0x04;
+
+/// Nanoseconds since the Unix epoch
+pub fn rtc_time_ns() -> u64 {
+    let base = usize::from(PhysAddr::from(RTC_BASE).kernel_va());
+    unsafe {
+        let low = read_volatile((base + TIME_LOW) as *const u32) as u64;
+        let high = read_volatile((base + TIME_HIGH) as *const u32) as u64;
+        (high << 32) | low
+    }
+}
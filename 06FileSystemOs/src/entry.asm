@@ -1,12 +1,61 @@
     .section .text.entry
     .globl _start
 _start:
-    la sp, boot_stack_top
-    call rust_main
+    # the boot hart starts here with its hartid in a0
+    la t3, rust_main
+    j _boot
+
+    .globl _start_secondary
+_start_secondary:
+    # other harts start here through SBI `hart_start`, with their hartid in a0
+    la t3, rust_main_secondary
+
+_boot:
+    # a hart beyond MAX_HARTS has no boot stack nor processor, so it never enters the kernel
+    li t0, {max_harts}
+    bgeu a0, t0, _park
+    # keep the hartid in tp, where the kernel looks for it
+    mv tp, a0
+    # turn on paging with the boot page table, which maps the kernel
+    # both at its physical address and in the high half
+    la t0, boot_page_table
+    srli t0, t0, 12
+    li t1, 8 << 60
+    or t0, t0, t1
+    csrw satp, t0
+    sfence.vma
+    # each hart gets its own boot stack: sp = boot_stack_lower_bound + (hartid + 1) * BOOT_STACK_SIZE
+    la sp, boot_stack_lower_bound
+    addi t1, a0, 1
+    li t2, {boot_stack_size}
+    mul t1, t1, t2
+    add sp, sp, t1
+    # `la` gives physical addresses until we jump to the high half
+    li t0, 0xffffffc000000000
+    add sp, sp, t0
+    add t3, t3, t0
+    jr t3
+
+_park:
+    wfi
+    j _park
 
     .section .bss.stack
     .globl boot_stack_lower_bound
 boot_stack_lower_bound:
-    .space 4096 * 16
+    # BOOT_STACK_SIZE bytes for each of MAX_HARTS harts
+    .space {boot_stack_size} * {max_harts}
     .globl boot_stack_top
-boot_stack_top:
\ No newline at end of file
+boot_stack_top:
+
+    .section .data
+    .align 12
+boot_page_table:
+    # 0x00000000_80000000 -> 0x80000000 (1G, VRWXAD)
+    .quad 0
+    .quad 0
+    .quad (0x80000 << 10) | 0xcf
+    .zero 8 * 255
+    # 0xffffffc0_80000000 -> 0x80000000 (1G, VRWXAD)
+    .quad (0x80000 << 10) | 0xcf
+    .zero 8 * 253
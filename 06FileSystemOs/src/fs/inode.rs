@@ -2,14 +2,15 @@
 //! we need to wrap `Inode` into `Arc`,but `Mutex` in `Inode` prevents
 //! file systems from being accessed simultaneously
 //!
-//! `UPSafeCell<OSInodeInner>` -> `OSInode`: for static `ROOT_INODE`,we
-//! need to wrap `OSInodeInner` into `UPSafeCell`
-use super::File;
+//! `SpinLock<OSInodeInner>` -> `OSInode`: for static `ROOT_INODE`,we
+//! need to wrap `OSInodeInner` into `SpinLock`
+use super::{File, SeekFrom, Stat, StatMode};
 use crate::drivers::BLOCK_DEVICE;
 use crate::mm::UserBuffer;
-use crate::sync::UPSafeCell;
+use crate::sync::SpinLock;
+use crate::syscall::Errno;
+use alloc::collections::BTreeMap;
 use alloc::sync::Arc;
-use alloc::vec::Vec;
 use bitflags::*;
 use easy_fs::{EasyFileSystem, Inode};
 use lazy_static::*;
@@ -18,37 +19,38 @@ use lazy_static::*;
 pub struct OSInode {
     readable: bool,
     writable: bool,
-    inner: UPSafeCell<OSInodeInner>,
+    append: bool,
+    inner: SpinLock<OSInodeInner>,
+    /// held while the file is open for writing
+    _write_access: Option<WriteAccess>,
 }
-/// The OS inode inner in 'UPSafeCell'
+/// The OS inode inner in 'SpinLock'
 pub struct OSInodeInner {
     offset: usize,
     inode: Arc<Inode>,
 }
 
 impl OSInode {
-    /// Construct an OS inode from a inode
-    pub fn new(readable: bool, writable: bool, inode: Arc<Inode>) -> Self {
-        Self {
+    /// Construct an OS inode from a inode,
+    /// or return `ETXTBSY` if it is writable and a program runs from the inode
+    pub fn new(
+        readable: bool,
+        writable: bool,
+        append: bool,
+        inode: Arc<Inode>,
+    ) -> Result<Self, Errno> {
+        let write_access = if writable {
+            Some(WriteAccess::get(&inode)?)
+        } else {
+            None
+        };
+        Ok(Self {
             readable,
             writable,
-            inner: unsafe { UPSafeCell::new(OSInodeInner { offset: 0, inode }) },
-        }
-    }
-    /// Read all data inside a inode into vector
-    pub fn read_all(&self) -> Vec<u8> {
-        let mut inner = self.inner.exclusive_access();
-        let mut buffer = [0u8; 512];
-        let mut v: Vec<u8> = Vec::new();
-        loop {
-            let len = inner.inode.read_at(inner.offset, &mut buffer);
-            if len == 0 {
-                break;
-            }
-            inner.offset += len;
-            v.extend_from_slice(&buffer[..len]);
-        }
-        v
+            append,
+            inner: SpinLock::new(OSInodeInner { offset: 0, inode }),
+            _write_access: write_access,
+        })
     }
 }
 
@@ -57,6 +59,54 @@ lazy_static! {
         let efs = EasyFileSystem::open(BLOCK_DEVICE.clone());
         Arc::new(EasyFileSystem::root_inode(&efs))
     };
+    /// By inode id, the number of holders of write access if positive,
+    /// or of programs running from the inode if negative, like `i_writecount` in Linux
+    static ref WRITE_COUNTS: SpinLock<BTreeMap<u32, isize>> = SpinLock::new(BTreeMap::new());
+}
+
+/// Write access to an inode, or a denial of it that keeps the inode
+/// unchanged while a program runs from it and its pages are read on first touch
+pub struct WriteAccess {
+    inode_id: u32,
+    /// `1` for write access, `-1` for a denial
+    delta: isize,
+}
+
+impl WriteAccess {
+    /// Get write access to `inode`, or `ETXTBSY` if it is denied
+    pub fn get(inode: &Inode) -> Result<Self, Errno> {
+        Self::new(inode.inode_id(), 1)
+    }
+    /// Deny write access to `inode`, or `ETXTBSY` if someone holds it
+    pub fn deny(inode: &Inode) -> Result<Self, Errno> {
+        Self::new(inode.inode_id(), -1)
+    }
+    fn new(inode_id: u32, delta: isize) -> Result<Self, Errno> {
+        let mut counts = WRITE_COUNTS.exclusive_access();
+        let count = counts.entry(inode_id).or_insert(0);
+        if *count * delta < 0 {
+            return Err(Errno::ETXTBSY);
+        }
+        *count += delta;
+        Ok(Self { inode_id, delta })
+    }
+}
+
+impl Clone for WriteAccess {
+    fn clone(&self) -> Self {
+        Self::new(self.inode_id, self.delta).unwrap()
+    }
+}
+
+impl Drop for WriteAccess {
+    fn drop(&mut self) {
+        let mut counts = WRITE_COUNTS.exclusive_access();
+        let count = counts.get_mut(&self.inode_id).unwrap();
+        *count -= self.delta;
+        if *count == 0 {
+            counts.remove(&self.inode_id);
+        }
+    }
 }
 /// List all files in the filesystems
 pub fn list_apps() {
@@ -76,10 +126,16 @@ bitflags! {
         const WRONLY = 1 << 0;
         ///Read & Write
         const RDWR = 1 << 1;
+        ///Fail if the file already exists, used with `CREATE`
+        const EXCL = 1 << 7;
         ///Allow create
         const CREATE = 1 << 9;
         ///Clear file and return an empty one
         const TRUNC = 1 << 10;
+        ///Move to the end of file before every write
+        const APPEND = 1 << 11;
+        ///Fail if the file is not a directory
+        const DIRECTORY = 1 << 16;
     }
 }
 
@@ -87,68 +143,211 @@ impl OpenFlags {
     /// Do not check validity for simplicity
     /// Return (readable, writable)
     pub fn read_write(&self) -> (bool, bool) {
-        if self.is_empty() {
-            (true, false)
-        } else if self.contains(Self::WRONLY) {
+        if self.contains(Self::WRONLY) {
             (false, true)
-        } else {
+        } else if self.contains(Self::RDWR) {
             (true, true)
+        } else {
+            (true, false)
         }
     }
 }
+/// Walk `path` from the root directory, component by component
+fn find_inode(path: &str) -> Option<Arc<Inode>> {
+    path.split('/')
+        .filter(|name| !name.is_empty())
+        .try_fold(ROOT_INODE.clone(), |dir, name| {
+            if dir.is_dir() {
+                dir.find(name)
+            } else {
+                None
+            }
+        })
+}
+/// Split `path` into the inode of its parent directory and its last component
+fn find_parent(path: &str) -> Option<(Arc<Inode>, &str)> {
+    let path = path.trim_end_matches('/');
+    let (dir, name) = match path.rfind('/') {
+        Some(pos) => (&path[..pos], &path[pos + 1..]),
+        None => ("", path),
+    };
+    if name.is_empty() {
+        return None;
+    }
+    find_inode(dir)
+        .filter(|inode| inode.is_dir())
+        .map(|inode| (inode, name))
+}
 ///Open file with flags
-pub fn open_file(name: &str, flags: OpenFlags) -> Option<Arc<OSInode>> {
+pub fn open_file(path: &str, flags: OpenFlags) -> Result<Arc<OSInode>, Errno> {
     let (readable, writable) = flags.read_write();
-    if flags.contains(OpenFlags::CREATE) {
-        if let Some(inode) = ROOT_INODE.find(name) {
-            // clear size
-            inode.clear();
-            Some(Arc::new(OSInode::new(readable, writable, inode)))
-        } else {
-            // create file
-            ROOT_INODE
-                .create(name)
-                .map(|inode| Arc::new(OSInode::new(readable, writable, inode)))
+    let inode = if let Some(inode) = find_inode(path) {
+        if flags.contains(OpenFlags::CREATE | OpenFlags::EXCL) {
+            return Err(Errno::EEXIST);
         }
+        inode
+    } else if flags.contains(OpenFlags::CREATE) && !flags.contains(OpenFlags::DIRECTORY) {
+        // create file
+        let (parent, name) = find_parent(path).ok_or(Errno::ENOENT)?;
+        parent.create(name).ok_or(Errno::EEXIST)?
     } else {
-        ROOT_INODE.find(name).map(|inode| {
-            if flags.contains(OpenFlags::TRUNC) {
-                inode.clear();
-            }
-            Arc::new(OSInode::new(readable, writable, inode))
-        })
+        return Err(Errno::ENOENT);
+    };
+    if inode.is_dir() {
+        if writable {
+            return Err(Errno::EISDIR);
+        }
+    } else if flags.contains(OpenFlags::DIRECTORY) {
+        return Err(Errno::ENOTDIR);
+    }
+    let os_inode = OSInode::new(readable, writable, flags.contains(OpenFlags::APPEND), inode)?;
+    if writable && flags.contains(OpenFlags::TRUNC) {
+        // clear size
+        os_inode.inner.exclusive_access().inode.clear();
     }
+    Ok(Arc::new(os_inode))
 }
-
-impl File for OSInode {
-    fn readable(&self) -> bool {
-        self.readable
+///Create a directory
+pub fn mkdir(path: &str) -> Result<(), Errno> {
+    let (parent, name) = find_parent(path).ok_or(Errno::ENOENT)?;
+    parent.create_dir(name).map(|_| ()).ok_or(Errno::EEXIST)
+}
+///Remove an empty directory
+pub fn rmdir(path: &str) -> Result<(), Errno> {
+    let (parent, name) = find_parent(path).ok_or(Errno::ENOENT)?;
+    if name == "." || name == ".." {
+        return Err(Errno::EINVAL);
     }
-    fn writable(&self) -> bool {
-        self.writable
+    if !parent.find(name).ok_or(Errno::ENOENT)?.is_dir() {
+        return Err(Errno::ENOTDIR);
     }
-    fn read(&self, mut buf: UserBuffer) -> usize {
-        let mut inner = self.inner.exclusive_access();
+    if parent.rmdir(name) {
+        Ok(())
+    } else {
+        Err(Errno::ENOTEMPTY)
+    }
+}
+///Create a hard link `new_path` to the file at `old_path`
+pub fn link(old_path: &str, new_path: &str) -> Result<(), Errno> {
+    let old = find_inode(old_path).ok_or(Errno::ENOENT)?;
+    if old.is_dir() {
+        return Err(Errno::EPERM);
+    }
+    let (parent, name) = find_parent(new_path).ok_or(Errno::ENOENT)?;
+    if parent.link(&old, name) {
+        Ok(())
+    } else if old.nlink() == 0 {
+        // unlinked since it was found
+        Err(Errno::ENOENT)
+    } else {
+        Err(Errno::EEXIST)
+    }
+}
+///Remove a link to a file, and reclaim its blocks once no link is left
+pub fn unlink(path: &str) -> Result<(), Errno> {
+    let (parent, name) = find_parent(path).ok_or(Errno::ENOENT)?;
+    if parent.find(name).ok_or(Errno::ENOENT)?.is_dir() {
+        return Err(Errno::EISDIR);
+    }
+    if parent.unlink(name) {
+        Ok(())
+    } else {
+        Err(Errno::ENOENT)
+    }
+}
+
+impl OSInodeInner {
+    /// Read from `offset` of the inode to `buf`, return the size read
+    fn read_from(&self, mut offset: usize, mut buf: UserBuffer) -> usize {
         let mut total_read_size = 0usize;
         for slice in buf.buffers.iter_mut() {
-            let read_size = inner.inode.read_at(inner.offset, *slice);
+            let read_size = self.inode.read_at(offset, *slice);
             if read_size == 0 {
                 break;
             }
-            inner.offset += read_size;
+            offset += read_size;
             total_read_size += read_size;
         }
         total_read_size
     }
-    fn write(&self, buf: UserBuffer) -> usize {
-        let mut inner = self.inner.exclusive_access();
+    /// Write `buf` to `offset` of the inode, return the size written
+    fn write_to(&self, mut offset: usize, buf: UserBuffer) -> usize {
         let mut total_write_size = 0usize;
         for slice in buf.buffers.iter() {
-            let write_size = inner.inode.write_at(inner.offset, *slice);
+            let write_size = self.inode.write_at(offset, *slice);
             assert_eq!(write_size, slice.len());
-            inner.offset += write_size;
+            offset += write_size;
             total_write_size += write_size;
         }
         total_write_size
     }
 }
+
+impl File for OSInode {
+    fn readable(&self) -> bool {
+        self.readable
+    }
+    fn writable(&self) -> bool {
+        self.writable
+    }
+    fn read(&self, buf: UserBuffer) -> Result<usize, Errno> {
+        let mut inner = self.inner.exclusive_access();
+        let read_size = inner.read_from(inner.offset, buf);
+        inner.offset += read_size;
+        Ok(read_size)
+    }
+    fn write(&self, buf: UserBuffer) -> Result<usize, Errno> {
+        let mut inner = self.inner.exclusive_access();
+        if self.append {
+            inner.offset = inner.inode.size() as usize;
+        }
+        let write_size = inner.write_to(inner.offset, buf);
+        inner.offset += write_size;
+        Ok(write_size)
+    }
+    fn stat(&self) -> Stat {
+        let inner = self.inner.exclusive_access();
+        let mode = if inner.inode.is_dir() {
+            StatMode::DIR
+        } else {
+            StatMode::FILE
+        };
+        Stat {
+            dev: 0,
+            ino: inner.inode.inode_id() as u64,
+            mode,
+            nlink: inner.inode.nlink(),
+            size: inner.inode.size() as u64,
+        }
+    }
+    /// Seeking past the end is allowed, and a later write fills the gap with zeros
+    fn seek(&self, pos: SeekFrom) -> Result<usize, Errno> {
+        let mut inner = self.inner.exclusive_access();
+        let offset = match pos {
+            SeekFrom::Start(offset) => offset as isize,
+            SeekFrom::Current(delta) => inner.offset as isize + delta,
+            SeekFrom::End(delta) => inner.inode.size() as isize + delta,
+        };
+        if offset < 0 {
+            return Err(Errno::EINVAL);
+        }
+        inner.offset = offset as usize;
+        Ok(inner.offset)
+    }
+    fn read_at(&self, offset: usize, buf: UserBuffer) -> Result<usize, Errno> {
+        Ok(self.inner.exclusive_access().read_from(offset, buf))
+    }
+    /// Like `write`, a file opened with `APPEND` ignores `offset` and writes to the end
+    fn write_at(&self, offset: usize, buf: UserBuffer) -> Result<usize, Errno> {
+        let inner = self.inner.exclusive_access();
+        let offset = if self.append {
+            inner.inode.size() as usize
+        } else {
+            offset
+        };
+        Ok(inner.write_to(offset, buf))
+    }
+    fn inode(&self) -> Option<Arc<Inode>> {
+        Some(self.inner.exclusive_access().inode.clone())
+    }
+}
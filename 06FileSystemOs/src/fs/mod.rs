@@ -1,19 +1,91 @@
 //! File system in os
 mod inode;
+mod pipe;
 mod stdio;
 
 use crate::mm::UserBuffer;
+use crate::syscall::Errno;
+use alloc::sync::Arc;
+use easy_fs::Inode;
 /// File trait
 pub trait File: Send + Sync {
     /// If readable
     fn readable(&self) -> bool;
     /// If writable
     fn writable(&self) -> bool;
-    /// Read file to `UserBuffer`
-    fn read(&self, buf: UserBuffer) -> usize;
-    /// Write `UserBuffer` to file
-    fn write(&self, buf: UserBuffer) -> usize;
+    /// Read file to `UserBuffer`, or return `EINTR` if a signal arrived
+    /// while waiting for data before any was read
+    fn read(&self, buf: UserBuffer) -> Result<usize, Errno>;
+    /// Write `UserBuffer` to file, or return `EINTR` if a signal arrived
+    /// while waiting for room before anything was written
+    fn write(&self, buf: UserBuffer) -> Result<usize, Errno>;
+    /// Get metadata of file
+    fn stat(&self) -> Stat;
+    /// Move the file offset and return the new one,
+    /// or `ESPIPE` if the file is not seekable
+    fn seek(&self, _pos: SeekFrom) -> Result<usize, Errno> {
+        Err(Errno::ESPIPE)
+    }
+    /// Read file at `offset` to `UserBuffer` without moving the file offset
+    fn read_at(&self, _offset: usize, _buf: UserBuffer) -> Result<usize, Errno> {
+        Err(Errno::ESPIPE)
+    }
+    /// Write `UserBuffer` to file at `offset` without moving the file offset
+    fn write_at(&self, _offset: usize, _buf: UserBuffer) -> Result<usize, Errno> {
+        Err(Errno::ESPIPE)
+    }
+    /// The inode of the file, or `None` if the file can not be mapped into memory
+    fn inode(&self) -> Option<Arc<Inode>> {
+        None
+    }
 }
 
-pub use inode::{list_apps, open_file, OSInode, OpenFlags};
+/// Position to move the file offset to
+#[derive(Copy, Clone, Debug)]
+pub enum SeekFrom {
+    /// From the start of file
+    Start(usize),
+    /// From the current offset
+    Current(isize),
+    /// From the end of file
+    End(isize),
+}
+
+/// The stat of a inode
+#[repr(C)]
+#[derive(Debug)]
+pub struct Stat {
+    /// ID of device containing file
+    pub dev: u64,
+    /// inode number
+    pub ino: u64,
+    /// file type and mode
+    pub mode: StatMode,
+    /// number of hard links
+    pub nlink: u32,
+    /// total size in bytes
+    pub size: u64,
+}
+
+bitflags! {
+    /// The mode of a inode
+    /// whether a directory or a file
+    pub struct StatMode: u32 {
+        /// null
+        const NULL  = 0;
+        /// pipe
+        const FIFO  = 0o010000;
+        /// character device
+        const CHR   = 0o020000;
+        /// directory
+        const DIR   = 0o040000;
+        /// ordinary regular file
+        const FILE  = 0o100000;
+    }
+}
+
+pub use inode::{
+    link, list_apps, mkdir, open_file, rmdir, unlink, OSInode, OpenFlags, WriteAccess,
+};
+pub use pipe::{make_pipe, Pipe};
 pub use stdio::{Stdin, Stdout};
@@ -0,0 +1,219 @@
+//! Implementation of [`Pipe`]: a bounded ring buffer shared by a read end
+//! and a write end, which are opened by `sys_pipe` and can be handed to
+//! child processes through the `fd_table` copied on fork.
+use super::{File, Stat, StatMode};
+use crate::mm::UserBuffer;
+use crate::sync::SpinLock;
+use crate::syscall::Errno;
+use crate::task::{current_signal_pending, suspend_current_and_run_next};
+use alloc::sync::{Arc, Weak};
+
+/// One end of a pipe
+pub struct Pipe {
+    readable: bool,
+    writable: bool,
+    buffer: Arc<SpinLock<PipeRingBuffer>>,
+}
+
+impl Pipe {
+    /// Create the read end of a pipe from a buffer
+    pub fn read_end_with_buffer(buffer: Arc<SpinLock<PipeRingBuffer>>) -> Self {
+        Self {
+            readable: true,
+            writable: false,
+            buffer,
+        }
+    }
+    /// Create the write end of a pipe from a buffer
+    pub fn write_end_with_buffer(buffer: Arc<SpinLock<PipeRingBuffer>>) -> Self {
+        Self {
+            readable: false,
+            writable: true,
+            buffer,
+        }
+    }
+}
+
+const RING_BUFFER_SIZE: usize = 32;
+
+#[derive(Copy, Clone, PartialEq)]
+enum RingBufferStatus {
+    Full,
+    Empty,
+    Normal,
+}
+
+/// The ring buffer shared by both ends of a pipe
+pub struct PipeRingBuffer {
+    arr: [u8; RING_BUFFER_SIZE],
+    head: usize,
+    tail: usize,
+    status: RingBufferStatus,
+    read_end: Option<Weak<Pipe>>,
+    write_end: Option<Weak<Pipe>>,
+}
+
+impl PipeRingBuffer {
+    /// Create an empty ring buffer
+    pub fn new() -> Self {
+        Self {
+            arr: [0; RING_BUFFER_SIZE],
+            head: 0,
+            tail: 0,
+            status: RingBufferStatus::Empty,
+            read_end: None,
+            write_end: None,
+        }
+    }
+    /// Record the ends without keeping them alive
+    fn set_ends(&mut self, read_end: &Arc<Pipe>, write_end: &Arc<Pipe>) {
+        self.read_end = Some(Arc::downgrade(read_end));
+        self.write_end = Some(Arc::downgrade(write_end));
+    }
+    fn write_byte(&mut self, byte: u8) {
+        self.status = RingBufferStatus::Normal;
+        self.arr[self.tail] = byte;
+        self.tail = (self.tail + 1) % RING_BUFFER_SIZE;
+        if self.tail == self.head {
+            self.status = RingBufferStatus::Full;
+        }
+    }
+    fn read_byte(&mut self) -> u8 {
+        self.status = RingBufferStatus::Normal;
+        let c = self.arr[self.head];
+        self.head = (self.head + 1) % RING_BUFFER_SIZE;
+        if self.head == self.tail {
+            self.status = RingBufferStatus::Empty;
+        }
+        c
+    }
+    fn available_read(&self) -> usize {
+        if self.status == RingBufferStatus::Empty {
+            0
+        } else if self.tail > self.head {
+            self.tail - self.head
+        } else {
+            self.tail + RING_BUFFER_SIZE - self.head
+        }
+    }
+    fn available_write(&self) -> usize {
+        if self.status == RingBufferStatus::Full {
+            0
+        } else {
+            RING_BUFFER_SIZE - self.available_read()
+        }
+    }
+    fn all_read_ends_closed(&self) -> bool {
+        self.read_end.as_ref().unwrap().upgrade().is_none()
+    }
+    fn all_write_ends_closed(&self) -> bool {
+        self.write_end.as_ref().unwrap().upgrade().is_none()
+    }
+}
+
+/// Return (read_end, write_end)
+pub fn make_pipe() -> (Arc<Pipe>, Arc<Pipe>) {
+    let buffer = Arc::new(SpinLock::new(PipeRingBuffer::new()));
+    let read_end = Arc::new(Pipe::read_end_with_buffer(buffer.clone()));
+    let write_end = Arc::new(Pipe::write_end_with_buffer(buffer.clone()));
+    buffer.exclusive_access().set_ends(&read_end, &write_end);
+    (read_end, write_end)
+}
+
+impl File for Pipe {
+    fn readable(&self) -> bool {
+        self.readable
+    }
+    fn writable(&self) -> bool {
+        self.writable
+    }
+    /// Yield until some data arrives, then read as much as is available.
+    /// Return 0 once all write ends are closed and the buffer is drained.
+    fn read(&self, buf: UserBuffer) -> Result<usize, Errno> {
+        assert!(self.readable());
+        let want_to_read = buf.len();
+        if want_to_read == 0 {
+            return Ok(0);
+        }
+        let mut buf_iter = buf.into_iter();
+        let mut already_read = 0usize;
+        loop {
+            let mut ring_buffer = self.buffer.exclusive_access();
+            let loop_read = ring_buffer.available_read();
+            if loop_read == 0 {
+                if already_read > 0 || ring_buffer.all_write_ends_closed() {
+                    return Ok(already_read);
+                }
+                drop(ring_buffer);
+                if current_signal_pending() {
+                    return Err(Errno::EINTR);
+                }
+                suspend_current_and_run_next();
+                continue;
+            }
+            for _ in 0..loop_read {
+                if let Some(byte_ref) = buf_iter.next() {
+                    unsafe {
+                        *byte_ref = ring_buffer.read_byte();
+                    }
+                    already_read += 1;
+                    if already_read == want_to_read {
+                        return Ok(want_to_read);
+                    }
+                } else {
+                    return Ok(already_read);
+                }
+            }
+        }
+    }
+    /// Yield while the buffer is full until everything is written.
+    /// Stop early once all read ends are closed, or a signal arrives.
+    fn write(&self, buf: UserBuffer) -> Result<usize, Errno> {
+        assert!(self.writable());
+        let want_to_write = buf.len();
+        if want_to_write == 0 {
+            return Ok(0);
+        }
+        let mut buf_iter = buf.into_iter();
+        let mut already_write = 0usize;
+        loop {
+            let mut ring_buffer = self.buffer.exclusive_access();
+            if ring_buffer.all_read_ends_closed() {
+                return Ok(already_write);
+            }
+            let loop_write = ring_buffer.available_write();
+            if loop_write == 0 {
+                drop(ring_buffer);
+                if current_signal_pending() {
+                    return if already_write > 0 {
+                        Ok(already_write)
+                    } else {
+                        Err(Errno::EINTR)
+                    };
+                }
+                suspend_current_and_run_next();
+                continue;
+            }
+            for _ in 0..loop_write {
+                if let Some(byte_ref) = buf_iter.next() {
+                    ring_buffer.write_byte(unsafe { *byte_ref });
+                    already_write += 1;
+                    if already_write == want_to_write {
+                        return Ok(want_to_write);
+                    }
+                } else {
+                    return Ok(already_write);
+                }
+            }
+        }
+    }
+    fn stat(&self) -> Stat {
+        Stat {
+            dev: 0,
+            ino: 0,
+            mode: StatMode::FIFO,
+            nlink: 1,
+            size: self.buffer.exclusive_access().available_read() as u64,
+        }
+    }
+}
@@ -1,8 +1,9 @@
 //!Stdin & Stdout
-use super::File;
+use super::{File, Stat, StatMode};
 use crate::mm::UserBuffer;
 use crate::sbi::console_getchar;
-use crate::task::suspend_current_and_run_next;
+use crate::syscall::Errno;
+use crate::task::{current_signal_pending, suspend_current_and_run_next};
 ///Standard input
 pub struct Stdin;
 ///Standard output
@@ -15,13 +16,16 @@ impl File for Stdin {
     fn writable(&self) -> bool {
         false
     }
-    fn read(&self, mut user_buf: UserBuffer) -> usize {
+    fn read(&self, mut user_buf: UserBuffer) -> Result<usize, Errno> {
         assert_eq!(user_buf.len(), 1);
         // busy loop
         let mut c: usize;
         loop {
             c = console_getchar();
             if c == 0 {
+                if current_signal_pending() {
+                    return Err(Errno::EINTR);
+                }
                 suspend_current_and_run_next();
                 continue;
             } else {
@@ -32,11 +36,14 @@ impl File for Stdin {
         unsafe {
             user_buf.buffers[0].as_mut_ptr().write_volatile(ch);
         }
-        1
+        Ok(1)
     }
-    fn write(&self, _user_buf: UserBuffer) -> usize {
+    fn write(&self, _user_buf: UserBuffer) -> Result<usize, Errno> {
         panic!("Cannot write to stdin!");
     }
+    fn stat(&self) -> Stat {
+        console_stat()
+    }
 }
 
 impl File for Stdout {
@@ -46,13 +53,27 @@ impl File for Stdout {
     fn writable(&self) -> bool {
         true
     }
-    fn read(&self, _user_buf: UserBuffer) -> usize {
+    fn read(&self, _user_buf: UserBuffer) -> Result<usize, Errno> {
         panic!("Cannot read from stdout!");
     }
-    fn write(&self, user_buf: UserBuffer) -> usize {
+    fn write(&self, user_buf: UserBuffer) -> Result<usize, Errno> {
         for buffer in user_buf.buffers.iter() {
             print!("{}", core::str::from_utf8(*buffer).unwrap());
         }
-        user_buf.len()
+        Ok(user_buf.len())
+    }
+    fn stat(&self) -> Stat {
+        console_stat()
+    }
+}
+
+/// Both stdin and stdout are the console character device
+fn console_stat() -> Stat {
+    Stat {
+        dev: 0,
+        ino: 0,
+        mode: StatMode::CHR,
+        nlink: 1,
+        size: 0,
     }
 }
@@ -13,7 +13,8 @@
 //! The operating system also starts in this module. Kernel code starts
 //! executing from `entry.asm`, after which [`rust_main()`] is called to
 //! initialize various pieces of functionality. (See its source code for
-//! details.)
+//! details.) The boot hart then starts the other harts, which enter
+//! [`rust_main_secondary()`].
 //!
 //! We then call [`task::run_tasks()`] and for the first time go to
 //! userspace.
@@ -50,7 +51,11 @@ pub mod trap;
 
 use core::arch::global_asm;
 
-global_asm!(include_str!("entry.asm"));
+global_asm!(
+    include_str!("entry.asm"),
+    max_harts = const config::MAX_HARTS,
+    boot_stack_size = const config::BOOT_STACK_SIZE,
+);
 /// clear BSS segment
 fn clear_bss() {
     extern "C" {
@@ -64,17 +69,44 @@ fn clear_bss() {
 }
 
 #[no_mangle]
-/// the rust entry-point of os
-pub fn rust_main() -> ! {
+/// the rust entry-point of os, run by the boot hart
+pub fn rust_main(hartid: usize) -> ! {
     clear_bss();
     println!("[kernel] Hello, world!");
     mm::init();
     mm::remap_test();
     trap::init();
     trap::enable_timer_interrupt();
+    timer::init();
     timer::set_next_trigger();
     fs::list_apps();
     task::add_initproc();
+    start_secondary_harts(hartid);
     task::run_tasks();
     panic!("Unreachable in rust_main!");
 }
+
+/// start the other harts at `_start_secondary`, now that the kernel is initialized
+fn start_secondary_harts(boot_hartid: usize) {
+    extern "C" {
+        fn _start_secondary();
+    }
+    let start_addr = mm::VirtAddr::from(_start_secondary as usize).kernel_pa();
+    for hartid in (0..config::MAX_HARTS).filter(|&id| id != boot_hartid) {
+        // harts qemu was not given with `-smp` just fail to start
+        if sbi::hart_start(hartid, start_addr.into(), 0) {
+            println!("[kernel] starting hart {}", hartid);
+        }
+    }
+}
+
+#[no_mangle]
+/// the rust entry-point of the other harts, run after the boot hart initialized the kernel
+pub fn rust_main_secondary(_hartid: usize) -> ! {
+    mm::KERNEL_SPACE.exclusive_access().activate();
+    trap::init();
+    trap::enable_timer_interrupt();
+    timer::set_next_trigger();
+    task::run_tasks();
+    panic!("Unreachable in rust_main_secondary!");
+}
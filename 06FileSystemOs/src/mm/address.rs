@@ -1,6 +1,6 @@
 //! Implementation of physical and virtual address and page number.
 use super::PageTableEntry;
-use crate::config::{PAGE_SIZE, PAGE_SIZE_BITS};
+use crate::config::{PAGE_SIZE, PAGE_SIZE_BITS, PHYS_VIRT_OFFSET};
 use core::fmt::{self, Debug, Formatter};
 
 const PA_WIDTH_SV39: usize = 56;
@@ -121,6 +121,10 @@ impl VirtAddr {
     pub fn aligned(&self) -> bool {
         self.page_offset() == 0
     }
+    ///Physical address of a kernel address in the linear mapping of physical memory
+    pub fn kernel_pa(&self) -> PhysAddr {
+        PhysAddr::from(usize::from(*self) - PHYS_VIRT_OFFSET)
+    }
 }
 impl From<VirtAddr> for VirtPageNum {
     fn from(v: VirtAddr) -> Self {
@@ -154,6 +158,10 @@ impl PhysAddr {
     pub fn aligned(&self) -> bool {
         self.page_offset() == 0
     }
+    ///Kernel address of `PhysAddr` in the linear mapping of physical memory
+    pub fn kernel_va(&self) -> VirtAddr {
+        VirtAddr::from(self.0 + PHYS_VIRT_OFFSET)
+    }
 }
 impl From<PhysAddr> for PhysPageNum {
     fn from(v: PhysAddr) -> Self {
@@ -183,23 +191,27 @@ impl VirtPageNum {
 impl PhysAddr {
     ///Get reference to `PhysAddr` value
     pub fn get_ref<T>(&self) -> &'static T {
-        unsafe { (self.0 as *const T).as_ref().unwrap() }
+        let va = usize::from(self.kernel_va());
+        unsafe { (va as *const T).as_ref().unwrap() }
     }
     ///Get mutable reference to `PhysAddr` value
     pub fn get_mut<T>(&self) -> &'static mut T {
-        unsafe { (self.0 as *mut T).as_mut().unwrap() }
+        let va = usize::from(self.kernel_va());
+        unsafe { (va as *mut T).as_mut().unwrap() }
     }
 }
 impl PhysPageNum {
     ///Get `PageTableEntry` on `PhysPageNum`
     pub fn get_pte_array(&self) -> &'static mut [PageTableEntry] {
         let pa: PhysAddr = (*self).into();
-        unsafe { core::slice::from_raw_parts_mut(pa.0 as *mut PageTableEntry, 512) }
+        let va = usize::from(pa.kernel_va());
+        unsafe { core::slice::from_raw_parts_mut(va as *mut PageTableEntry, 512) }
     }
     ///Get u8 array on `PhysPageNum`
     pub fn get_bytes_array(&self) -> &'static mut [u8] {
         let pa: PhysAddr = (*self).into();
-        unsafe { core::slice::from_raw_parts_mut(pa.0 as *mut u8, 4096) }
+        let va = usize::from(pa.kernel_va());
+        unsafe { core::slice::from_raw_parts_mut(va as *mut u8, 4096) }
     }
     ///Get Get mutable reference to `PhysAddr` value on `PhysPageNum`
     pub fn get_mut<T>(&self) -> &'static mut T {
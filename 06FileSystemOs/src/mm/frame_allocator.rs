@@ -1,8 +1,10 @@
 //! Implementation of [`FrameAllocator`] which
 //! controls all the frames in the operating system.
-use super::{PhysAddr, PhysPageNum};
+use super::swap::swap_out_one;
+use super::{PhysAddr, PhysPageNum, VirtAddr};
 use crate::config::MEMORY_END;
-use crate::sync::UPSafeCell;
+use crate::sync::SpinLock;
+use alloc::collections::BTreeSet;
 use alloc::vec::Vec;
 use core::fmt::{self, Debug, Formatter};
 use lazy_static::*;
@@ -40,57 +42,110 @@ impl Drop for FrameTracker {
 trait FrameAllocator {
     fn new() -> Self;
     fn alloc(&mut self) -> Option<PhysPageNum>;
+    fn alloc_contiguous(&mut self, count: usize, align: usize) -> Option<PhysPageNum>;
     fn dealloc(&mut self, ppn: PhysPageNum);
 }
-/// an implementation for frame allocator
-pub struct StackFrameAllocator {
-    current: usize,
+
+/// the largest block has 2^MAX_ORDER frames, 4GiB
+const MAX_ORDER: usize = 20;
+
+/// Order of the largest power of two not greater than `n`
+fn floor_order(n: usize) -> usize {
+    (usize::BITS - 1 - n.leading_zeros()) as usize
+}
+
+/// an implementation for frame allocator with the buddy system
+pub struct BuddyFrameAllocator {
+    /// free blocks of 2^order frames by the first ppn, for each order
+    free_lists: Vec<BTreeSet<usize>>,
+    start: usize,
     end: usize,
-    recycled: Vec<usize>,
+    free: usize,
 }
 
-impl StackFrameAllocator {
+impl BuddyFrameAllocator {
     pub fn init(&mut self, l: PhysPageNum, r: PhysPageNum) {
-        self.current = l.0;
+        self.start = l.0;
         self.end = r.0;
-        println!("last {} Physical Frames.", self.end - self.current);
+        self.free_range(l.0, r.0);
+    }
+    /// Free `[l, r)` as the largest aligned blocks
+    fn free_range(&mut self, l: usize, r: usize) {
+        let mut current = l;
+        while current < r {
+            let order = (current.trailing_zeros() as usize)
+                .min(floor_order(r - current))
+                .min(MAX_ORDER);
+            self.dealloc_block(current, order);
+            current += 1 << order;
+        }
+    }
+    fn alloc_block(&mut self, order: usize) -> Option<usize> {
+        let found = (order..=MAX_ORDER).find(|&o| !self.free_lists[o].is_empty())?;
+        let start = *self.free_lists[found].iter().next().unwrap();
+        self.free_lists[found].remove(&start);
+        // split, giving back the upper halves
+        for o in (order..found).rev() {
+            self.free_lists[o].insert(start + (1 << o));
+        }
+        self.free -= 1 << order;
+        Some(start)
+    }
+    fn dealloc_block(&mut self, mut start: usize, mut order: usize) {
+        self.free += 1 << order;
+        // merge with the buddy as long as it is free
+        while order < MAX_ORDER && self.free_lists[order].remove(&(start ^ (1 << order))) {
+            start &= !(1 << order);
+            order += 1;
+        }
+        self.free_lists[order].insert(start);
+    }
+    /// Whether `ppn` is in a free block
+    fn is_free(&self, ppn: usize) -> bool {
+        (0..=MAX_ORDER).any(|o| self.free_lists[o].contains(&(ppn & !((1 << o) - 1))))
     }
 }
-impl FrameAllocator for StackFrameAllocator {
+impl FrameAllocator for BuddyFrameAllocator {
     fn new() -> Self {
         Self {
-            current: 0,
+            free_lists: (0..=MAX_ORDER).map(|_| BTreeSet::new()).collect(),
+            start: 0,
             end: 0,
-            recycled: Vec::new(),
+            free: 0,
         }
     }
     fn alloc(&mut self) -> Option<PhysPageNum> {
-        if let Some(ppn) = self.recycled.pop() {
-            Some(ppn.into())
-        } else if self.current == self.end {
-            None
-        } else {
-            self.current += 1;
-            Some((self.current - 1).into())
+        self.alloc_block(0).map(|ppn| ppn.into())
+    }
+    fn alloc_contiguous(&mut self, count: usize, align: usize) -> Option<PhysPageNum> {
+        if count == 0 || count > 1 << MAX_ORDER || !align.is_power_of_two() {
+            return None;
+        }
+        // a block is aligned to its size
+        let order = floor_order(count.next_power_of_two()).max(floor_order(align));
+        if order > MAX_ORDER {
+            return None;
         }
+        let start = self.alloc_block(order)?;
+        self.free_range(start + count, start + (1 << order));
+        Some(start.into())
     }
     fn dealloc(&mut self, ppn: PhysPageNum) {
         let ppn = ppn.0;
         // validity check
-        if ppn >= self.current || self.recycled.iter().any(|&v| v == ppn) {
+        if ppn < self.start || ppn >= self.end || self.is_free(ppn) {
             panic!("Frame ppn={:#x} has not been allocated!", ppn);
         }
-        // recycle
-        self.recycled.push(ppn);
+        self.dealloc_block(ppn, 0);
     }
 }
 
-type FrameAllocatorImpl = StackFrameAllocator;
+type FrameAllocatorImpl = BuddyFrameAllocator;
 
 lazy_static! {
     /// frame allocator instance through lazy_static!
-    pub static ref FRAME_ALLOCATOR: UPSafeCell<FrameAllocatorImpl> =
-        unsafe { UPSafeCell::new(FrameAllocatorImpl::new()) };
+    pub static ref FRAME_ALLOCATOR: SpinLock<FrameAllocatorImpl> =
+        SpinLock::new(FrameAllocatorImpl::new());
 }
 /// initiate the frame allocator using `ekernel` and `MEMORY_END`
 pub fn init_frame_allocator() {
@@ -98,16 +153,54 @@ pub fn init_frame_allocator() {
         fn ekernel();
     }
     FRAME_ALLOCATOR.exclusive_access().init(
-        PhysAddr::from(ekernel as usize).ceil(),
+        VirtAddr::from(ekernel as usize).kernel_pa().ceil(),
         PhysAddr::from(MEMORY_END).floor(),
     );
+    println!("last {} Physical Frames.", frame_stats().total);
 }
-/// allocate a frame
+/// allocate a frame, evicting user pages to swap while there is none left
 pub fn frame_alloc() -> Option<FrameTracker> {
-    FRAME_ALLOCATOR
-        .exclusive_access()
-        .alloc()
-        .map(FrameTracker::new)
+    loop {
+        let ppn = FRAME_ALLOCATOR.exclusive_access().alloc();
+        if let Some(ppn) = ppn {
+            return Some(FrameTracker::new(ppn));
+        }
+        if !swap_out_one() {
+            return None;
+        }
+    }
+}
+/// allocate `count` contiguous frames, the first of which is aligned to `align` frames
+pub fn frame_alloc_contiguous(count: usize, align: usize) -> Option<Vec<FrameTracker>> {
+    loop {
+        let ppn = FRAME_ALLOCATOR
+            .exclusive_access()
+            .alloc_contiguous(count, align);
+        if let Some(ppn) = ppn {
+            return Some(
+                (ppn.0..ppn.0 + count)
+                    .map(|ppn| FrameTracker::new(ppn.into()))
+                    .collect(),
+            );
+        }
+        if !swap_out_one() {
+            return None;
+        }
+    }
+}
+/// Numbers of frames managed by the frame allocator
+#[derive(Copy, Clone, Debug)]
+pub struct FrameStats {
+    pub total: usize,
+    pub free: usize,
+}
+/// get the numbers of all frames and of free frames
+pub fn frame_stats() -> FrameStats {
+    let allocator = FRAME_ALLOCATOR.exclusive_access();
+    FrameStats {
+        total: allocator.end - allocator.start,
+        free: allocator.free,
+    }
 }
 /// deallocate a frame
 pub fn frame_dealloc(ppn: PhysPageNum) {
@@ -130,5 +223,14 @@ pub fn frame_allocator_test() {
         v.push(frame);
     }
     drop(v);
+    let free = frame_stats().free;
+    let v = frame_alloc_contiguous(5, 8).unwrap();
+    assert_eq!(v[0].ppn.0 % 8, 0);
+    for (i, frame) in v.iter().enumerate() {
+        assert_eq!(frame.ppn.0, v[0].ppn.0 + i);
+    }
+    assert_eq!(frame_stats().free, free - 5);
+    drop(v);
+    assert_eq!(frame_stats().free, free);
     println!("frame_allocator_test passed!");
 }
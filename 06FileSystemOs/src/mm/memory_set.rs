@@ -1,14 +1,22 @@
 //! Implementation of [`MapArea`] and [`MemorySet`].
-use super::{frame_alloc, FrameTracker};
-use super::{PTEFlags, PageTable, PageTableEntry};
+use super::frame_alloc;
+use super::swap::Page;
+use super::{level_pages, PTEFlags, PageTable, PageTableEntry};
 use super::{PhysAddr, PhysPageNum, VirtAddr, VirtPageNum};
 use super::{StepByOne, VPNRange};
-use crate::config::{MEMORY_END, MMIO, PAGE_SIZE, TRAMPOLINE, TRAP_CONTEXT, USER_STACK_SIZE};
-use crate::sync::UPSafeCell;
+use crate::config::{
+    MEMORY_END, MMAP_BASE, MMIO, PAGE_SIZE, TRAMPOLINE, TRAP_CONTEXT, USER_SPACE_END,
+    USER_STACK_SIZE,
+};
+use crate::fs::WriteAccess;
+use crate::sync::SpinLock;
+use crate::syscall::Errno;
 use alloc::collections::BTreeMap;
 use alloc::sync::Arc;
+use alloc::vec;
 use alloc::vec::Vec;
 use core::arch::asm;
+use easy_fs::Inode;
 use lazy_static::*;
 use riscv::register::satp;
 
@@ -27,17 +35,22 @@ extern "C" {
 
 lazy_static! {
     /// a memory set instance through lazy_static! managing kernel space
-    pub static ref KERNEL_SPACE: Arc<UPSafeCell<MemorySet>> =
-        Arc::new(unsafe { UPSafeCell::new(MemorySet::new_kernel()) });
+    pub static ref KERNEL_SPACE: Arc<SpinLock<MemorySet>> =
+        Arc::new(SpinLock::new(MemorySet::new_kernel()));
+    /// the kernel page table never moves, and the block device driver asks for
+    /// it while swapping out pages with `KERNEL_SPACE` borrowed
+    static ref KERNEL_TOKEN: usize = KERNEL_SPACE.exclusive_access().token();
 }
 ///Get kernelspace root ppn
 pub fn kernel_token() -> usize {
-    KERNEL_SPACE.exclusive_access().token()
+    *KERNEL_TOKEN
 }
 /// memory set structure, controls virtual-memory space
 pub struct MemorySet {
-    page_table: PageTable,
+    /// dropped before `page_table`, as pages that may be swapped out
+    /// refer to their PTEs
     areas: Vec<MapArea>,
+    page_table: PageTable,
 }
 
 impl MemorySet {
@@ -64,6 +77,76 @@ impl MemorySet {
             None,
         );
     }
+    /// Assume that no conflicts. Pages are mapped on first touch,
+    /// filled from `backing` or with zeros.
+    pub fn insert_lazy_area(
+        &mut self,
+        start_va: VirtAddr,
+        end_va: VirtAddr,
+        permission: MapPermission,
+        shared: bool,
+        backing: Option<MapBacking>,
+    ) {
+        let mut map_area = MapArea::new(start_va, end_va, MapType::Lazy, permission);
+        map_area.backing = backing;
+        map_area.shared = shared;
+        self.push(map_area, None);
+    }
+    /// Whether `[start, end)` is in user space and overlaps no area
+    pub fn is_free(&self, start: VirtPageNum, end: VirtPageNum) -> bool {
+        start.0 > 0
+            && start < end
+            && end.0 <= USER_SPACE_END / PAGE_SIZE
+            && self
+                .areas
+                .iter()
+                .all(|area| area.vpn_range.get_end() <= start || end <= area.vpn_range.get_start())
+    }
+    /// Find the lowest free range of `page_count` pages from `MMAP_BASE`
+    pub fn find_free_area(&self, page_count: usize) -> Option<VirtPageNum> {
+        let mut start = VirtPageNum(MMAP_BASE / PAGE_SIZE);
+        loop {
+            let end = VirtPageNum(start.0 + page_count);
+            if end.0 > USER_SPACE_END / PAGE_SIZE {
+                return None;
+            }
+            match self
+                .areas
+                .iter()
+                .find(|area| start < area.vpn_range.get_end() && area.vpn_range.get_start() < end)
+            {
+                Some(area) => start = area.vpn_range.get_end(),
+                None => return Some(start),
+            }
+        }
+    }
+    /// Unmap the pages in `[start, end)`, splitting the areas across its ends.
+    /// Shared pages are written back first.
+    pub fn munmap(&mut self, start: VirtPageNum, end: VirtPageNum) {
+        for mut area in core::mem::take(&mut self.areas) {
+            if area.vpn_range.get_end() <= start || end <= area.vpn_range.get_start() {
+                self.areas.push(area);
+                continue;
+            }
+            if area.vpn_range.get_start() < start {
+                let rest = area.split_off(start);
+                self.areas.push(area);
+                area = rest;
+            }
+            if end < area.vpn_range.get_end() {
+                let rest = area.split_off(end);
+                self.areas.push(rest);
+            }
+            area.write_back(start, end);
+            area.unmap(&mut self.page_table);
+        }
+    }
+    /// Write the shared pages in `[start, end)` back to their files
+    pub fn msync(&self, start: VirtPageNum, end: VirtPageNum) {
+        for area in self.areas.iter() {
+            area.write_back(start, end);
+        }
+    }
     ///Remove `MapArea` that starts with `start_vpn`
     pub fn remove_area_with_start_vpn(&mut self, start_vpn: VirtPageNum) {
         if let Some((idx, area)) = self
@@ -87,7 +170,7 @@ impl MemorySet {
     fn map_trampoline(&mut self) {
         self.page_table.map(
             VirtAddr::from(TRAMPOLINE).into(),
-            PhysAddr::from(strampoline as usize).into(),
+            VirtAddr::from(strampoline as usize).kernel_pa().into(),
             PTEFlags::R | PTEFlags::X,
         );
     }
@@ -109,7 +192,7 @@ impl MemorySet {
             MapArea::new(
                 (stext as usize).into(),
                 (etext as usize).into(),
-                MapType::Identical,
+                MapType::Linear,
                 MapPermission::R | MapPermission::X,
             ),
             None,
@@ -119,7 +202,7 @@ impl MemorySet {
             MapArea::new(
                 (srodata as usize).into(),
                 (erodata as usize).into(),
-                MapType::Identical,
+                MapType::Linear,
                 MapPermission::R,
             ),
             None,
@@ -129,7 +212,7 @@ impl MemorySet {
             MapArea::new(
                 (sdata as usize).into(),
                 (edata as usize).into(),
-                MapType::Identical,
+                MapType::Linear,
                 MapPermission::R | MapPermission::W,
             ),
             None,
@@ -139,7 +222,7 @@ impl MemorySet {
             MapArea::new(
                 (sbss_with_stack as usize).into(),
                 (ebss as usize).into(),
-                MapType::Identical,
+                MapType::Linear,
                 MapPermission::R | MapPermission::W,
             ),
             None,
@@ -148,8 +231,8 @@ impl MemorySet {
         memory_set.push(
             MapArea::new(
                 (ekernel as usize).into(),
-                MEMORY_END.into(),
-                MapType::Identical,
+                PhysAddr::from(MEMORY_END).kernel_va(),
+                MapType::Linear,
                 MapPermission::R | MapPermission::W,
             ),
             None,
@@ -158,9 +241,9 @@ impl MemorySet {
         for pair in MMIO {
             memory_set.push(
                 MapArea::new(
-                    (*pair).0.into(),
-                    ((*pair).0 + (*pair).1).into(),
-                    MapType::Identical,
+                    PhysAddr::from((*pair).0).kernel_va(),
+                    PhysAddr::from((*pair).0 + (*pair).1).kernel_va(),
+                    MapType::Linear,
                     MapPermission::R | MapPermission::W,
                 ),
                 None,
@@ -170,22 +253,48 @@ impl MemorySet {
     }
     /// Include sections in elf and trampoline and TrapContext and user stack,
     /// also returns user_sp and entry point.
-    pub fn from_elf(elf_data: &[u8]) -> (Self, usize, usize) {
+    /// Only the elf headers are read here, sections are read on first touch,
+    /// so the elf can not be written while mapped, and `ETXTBSY` is returned
+    /// if it is open for writing. Return `ENOEXEC` if the file is not a valid elf.
+    pub fn from_elf(elf_inode: &Arc<Inode>) -> Result<(Self, usize, usize), Errno> {
+        let write_access = WriteAccess::deny(elf_inode)?;
+        // read elf header and program headers
+        let mut header = [0u8; 64];
+        if elf_inode.read_at(0, &mut header) < header.len() {
+            return Err(Errno::ENOEXEC);
+        }
+        let pt2 = xmas_elf::header::parse_header(&header)
+            .map_err(|_| Errno::ENOEXEC)?
+            .pt2;
+        let ph_end = (pt2.ph_count() as usize)
+            .checked_mul(pt2.ph_entry_size() as usize)
+            .and_then(|size| size.checked_add(pt2.ph_offset() as usize))
+            .filter(|&end| end <= elf_inode.size() as usize)
+            .ok_or(Errno::ENOEXEC)?;
+        let mut elf_data = vec![0u8; ph_end];
+        elf_inode.read_at(0, &mut elf_data);
+        let elf = xmas_elf::ElfFile::new(&elf_data).map_err(|_| Errno::ENOEXEC)?;
+        let elf_header = elf.header;
+        let magic = elf_header.pt1.magic;
+        if magic != [0x7f, 0x45, 0x4c, 0x46] {
+            return Err(Errno::ENOEXEC);
+        }
         let mut memory_set = Self::new_bare();
         // map trampoline
         memory_set.map_trampoline();
         // map program headers of elf, with U flag
-        let elf = xmas_elf::ElfFile::new(elf_data).unwrap();
-        let elf_header = elf.header;
-        let magic = elf_header.pt1.magic;
-        assert_eq!(magic, [0x7f, 0x45, 0x4c, 0x46], "invalid elf!");
         let ph_count = elf_header.pt2.ph_count();
         let mut max_end_vpn = VirtPageNum(0);
         for i in 0..ph_count {
-            let ph = elf.program_header(i).unwrap();
-            if ph.get_type().unwrap() == xmas_elf::program::Type::Load {
+            let ph = elf.program_header(i).map_err(|_| Errno::ENOEXEC)?;
+            if ph.get_type() == Ok(xmas_elf::program::Type::Load) {
+                let end = ph
+                    .virtual_addr()
+                    .checked_add(ph.mem_size())
+                    .filter(|&end| end as usize <= USER_SPACE_END)
+                    .ok_or(Errno::ENOEXEC)?;
                 let start_va: VirtAddr = (ph.virtual_addr() as usize).into();
-                let end_va: VirtAddr = ((ph.virtual_addr() + ph.mem_size()) as usize).into();
+                let end_va: VirtAddr = (end as usize).into();
                 let mut map_perm = MapPermission::U;
                 let ph_flags = ph.flags();
                 if ph_flags.is_read() {
@@ -197,12 +306,15 @@ impl MemorySet {
                 if ph_flags.is_execute() {
                     map_perm |= MapPermission::X;
                 }
-                let map_area = MapArea::new(start_va, end_va, MapType::Framed, map_perm);
+                let map_area = MapArea::new(start_va, end_va, MapType::Lazy, map_perm)
+                    .with_backing(MapBacking {
+                        inode: elf_inode.clone(),
+                        offset: ph.offset() as usize,
+                        len: ph.file_size() as usize,
+                        write_access: Some(write_access.clone()),
+                    });
                 max_end_vpn = map_area.vpn_range.get_end();
-                memory_set.push(
-                    map_area,
-                    Some(&elf.input[ph.offset() as usize..(ph.offset() + ph.file_size()) as usize]),
-                );
+                memory_set.push(map_area, None);
             }
         }
         // map user stack with U flags
@@ -215,7 +327,17 @@ impl MemorySet {
             MapArea::new(
                 user_stack_bottom.into(),
                 user_stack_top.into(),
-                MapType::Framed,
+                MapType::Lazy,
+                MapPermission::R | MapPermission::W | MapPermission::U,
+            ),
+            None,
+        );
+        // used in sbrk
+        memory_set.push(
+            MapArea::new(
+                user_stack_top.into(),
+                user_stack_top.into(),
+                MapType::Lazy,
                 MapPermission::R | MapPermission::W | MapPermission::U,
             ),
             None,
@@ -230,32 +352,133 @@ impl MemorySet {
             ),
             None,
         );
-        (
+        Ok((
             memory_set,
             user_stack_top,
             elf.header.pt2.entry_point() as usize,
-        )
+        ))
     }
-    ///Clone a same `MemorySet`
-    pub fn from_existed_user(user_space: &MemorySet) -> MemorySet {
+    ///Clone a same `MemorySet`, sharing the frames of user pages copy-on-write
+    pub fn from_existed_user(user_space: &mut MemorySet) -> MemorySet {
         let mut memory_set = Self::new_bare();
         // map trampoline
         memory_set.map_trampoline();
-        // copy data sections/trap_context/user_stack
-        for area in user_space.areas.iter() {
-            let new_area = MapArea::from_another(area);
-            memory_set.push(new_area, None);
-            // copy data from another space
-            for vpn in area.vpn_range {
-                let src_ppn = user_space.translate(vpn).unwrap().ppn();
-                let dst_ppn = memory_set.translate(vpn).unwrap().ppn();
-                dst_ppn
-                    .get_bytes_array()
-                    .copy_from_slice(src_ppn.get_bytes_array());
+        // share data sections/user_stack, copy trap_context
+        for area in user_space.areas.iter_mut() {
+            let mut new_area = MapArea::from_another(area);
+            if area.shared {
+                // both spaces keep writing to the same frames, which only works
+                // for frames that exist at fork, so touch every page of the area now
+                for vpn in area.vpn_range {
+                    if !area.data_frames.contains_key(&vpn) {
+                        area.map_one(&mut user_space.page_table, vpn);
+                    }
+                }
+                let pte_flags = PTEFlags::from_bits(area.map_perm.bits).unwrap();
+                for (vpn, page) in area.data_frames.iter() {
+                    if let Some(ppn) = page.ppn() {
+                        memory_set.page_table.map(*vpn, ppn, pte_flags);
+                    }
+                    new_area.data_frames.insert(*vpn, Arc::clone(page));
+                }
+                memory_set.areas.push(new_area);
+            } else if area.map_perm.contains(MapPermission::U) {
+                // both spaces map the frames read-only until one of them writes
+                let pte_flags =
+                    PTEFlags::from_bits((area.map_perm - MapPermission::W).bits).unwrap();
+                for (vpn, page) in area.data_frames.iter() {
+                    // a shared page can not be swapped out, as only one PTE is known
+                    page.set_owner(None);
+                    // a page in swap is mapped by each space on its next fault
+                    if let Some(ppn) = page.ppn() {
+                        user_space.page_table.unmap(*vpn);
+                        user_space.page_table.map(*vpn, ppn, pte_flags);
+                        memory_set.page_table.map(*vpn, ppn, pte_flags);
+                    }
+                    new_area.data_frames.insert(*vpn, Arc::clone(page));
+                }
+                memory_set.areas.push(new_area);
+            } else {
+                // the kernel writes TrapContext through its physical address
+                memory_set.push(new_area, None);
+                for vpn in area.vpn_range {
+                    let src_ppn = user_space.page_table.translate(vpn).unwrap().ppn();
+                    let dst_ppn = memory_set.translate(vpn).unwrap().ppn();
+                    dst_ppn
+                        .get_bytes_array()
+                        .copy_from_slice(src_ppn.get_bytes_array());
+                }
             }
         }
         memory_set
     }
+    /// Handle a page fault at `vpn`: map a lazy page on first touch,
+    /// bring a page back from swap, or copy a page shared copy-on-write on a store.
+    /// Return false if the access is not allowed. Accesses are made for user mode,
+    /// so pages without `U`, like `TRAP_CONTEXT`, and the trampoline, which is
+    /// in no area, are never allowed.
+    pub fn handle_page_fault(&mut self, vpn: VirtPageNum, write: bool) -> bool {
+        let area = match self
+            .areas
+            .iter_mut()
+            .find(|area| area.vpn_range.get_start() <= vpn && vpn < area.vpn_range.get_end())
+        {
+            Some(area) if area.map_perm.contains(MapPermission::U) => area,
+            _ => return false,
+        };
+        if write && !area.map_perm.contains(MapPermission::W) {
+            return false;
+        }
+        if area.data_frames.contains_key(&vpn) {
+            if self
+                .page_table
+                .translate(vpn)
+                .map_or(false, |pte| pte.is_valid())
+            {
+                write && area.copy_on_write(&mut self.page_table, vpn)
+            } else {
+                area.map_resident(&mut self.page_table, vpn);
+                true
+            }
+        } else if area.map_type == MapType::Lazy {
+            area.map_one(&mut self.page_table, vpn);
+            true
+        } else {
+            false
+        }
+    }
+    /// Shrink the area that starts at `start` to end at `new_end`
+    pub fn shrink_to(&mut self, start: VirtAddr, new_end: VirtAddr) -> bool {
+        if let Some(area) = self
+            .areas
+            .iter_mut()
+            .find(|area| area.vpn_range.get_start() == start.floor())
+        {
+            area.shrink_to(&mut self.page_table, new_end.ceil());
+            true
+        } else {
+            false
+        }
+    }
+    /// Grow the area that starts at `start` to end at `new_end`,
+    /// unless that would overlap another area or leave user space
+    pub fn append_to(&mut self, start: VirtAddr, new_end: VirtAddr) -> bool {
+        let new_end = new_end.ceil();
+        let idx = match self
+            .areas
+            .iter()
+            .position(|area| area.vpn_range.get_start() == start.floor())
+        {
+            Some(idx) => idx,
+            None => return false,
+        };
+        let old_end = self.areas[idx].vpn_range.get_end();
+        if old_end < new_end && !self.is_free(old_end, new_end) {
+            return false;
+        }
+        self.areas[idx].append_to(&mut self.page_table, new_end);
+        true
+    }
     ///Refresh TLB with `sfence.vma`
     pub fn activate(&self) {
         let satp = self.page_table.token();
@@ -277,9 +500,24 @@ impl MemorySet {
 /// map area structure, controls a contiguous piece of virtual memory
 pub struct MapArea {
     vpn_range: VPNRange,
-    data_frames: BTreeMap<VirtPageNum, FrameTracker>,
+    /// pages are shared between address spaces after fork
+    data_frames: BTreeMap<VirtPageNum, Arc<Page>>,
     map_type: MapType,
     map_perm: MapPermission,
+    /// initial content of a lazy area, zeros if `None`
+    backing: Option<MapBacking>,
+    /// writes go back to `backing` and are seen by children after fork
+    shared: bool,
+}
+
+/// A range of a file that a lazy area starts with, the rest of the area is zeros
+#[derive(Clone)]
+pub struct MapBacking {
+    pub inode: Arc<Inode>,
+    pub offset: usize,
+    pub len: usize,
+    /// write access for areas written back, or its denial for elf sections
+    pub write_access: Option<WriteAccess>,
 }
 
 impl MapArea {
@@ -296,47 +534,226 @@ impl MapArea {
             data_frames: BTreeMap::new(),
             map_type,
             map_perm,
+            backing: None,
+            shared: false,
         }
     }
+    pub fn with_backing(mut self, backing: MapBacking) -> Self {
+        self.backing = Some(backing);
+        self
+    }
     pub fn from_another(another: &MapArea) -> Self {
         Self {
             vpn_range: VPNRange::new(another.vpn_range.get_start(), another.vpn_range.get_end()),
             data_frames: BTreeMap::new(),
             map_type: another.map_type,
             map_perm: another.map_perm,
+            backing: another.backing.clone(),
+            shared: another.shared,
+        }
+    }
+    /// Keep `[start, at)` and return the rest of the area
+    fn split_off(&mut self, at: VirtPageNum) -> Self {
+        let mut rest = Self::from_another(self);
+        rest.vpn_range = VPNRange::new(at, self.vpn_range.get_end());
+        rest.data_frames = self.data_frames.split_off(&at);
+        if let Some(backing) = &mut rest.backing {
+            let skipped = (at.0 - self.vpn_range.get_start().0) * PAGE_SIZE;
+            backing.offset += skipped;
+            backing.len = backing.len.saturating_sub(skipped);
+        }
+        self.vpn_range = VPNRange::new(self.vpn_range.get_start(), at);
+        rest
+    }
+    /// Write the touched pages in `[start, end)` of a shared writable area
+    /// back to its file
+    fn write_back(&self, start: VirtPageNum, end: VirtPageNum) {
+        if !self.shared || !self.map_perm.contains(MapPermission::W) || start >= end {
+            return;
+        }
+        let backing = match &self.backing {
+            Some(backing) => backing,
+            None => return,
+        };
+        for (vpn, page) in self.data_frames.range(start..end) {
+            let pos = (vpn.0 - self.vpn_range.get_start().0) * PAGE_SIZE;
+            // pages of shared areas are never swapped out
+            if let (true, Some(ppn)) = (pos < backing.len, page.ppn()) {
+                let len = (backing.len - pos).min(PAGE_SIZE);
+                backing
+                    .inode
+                    .write_at(backing.offset + pos, &ppn.get_bytes_array()[..len]);
+            }
+        }
+    }
+    /// Whether pages of the area may be swapped out: user pages that are
+    /// not written back to a file
+    fn swappable(&self) -> bool {
+        self.map_perm.contains(MapPermission::U) && !self.shared
+    }
+    /// PTE flags with all permissions of the area. Pages that may be swapped out
+    /// start accessed, so that the clock passes over them once.
+    fn pte_flags(&self) -> PTEFlags {
+        let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
+        if self.swappable() {
+            pte_flags | PTEFlags::A
+        } else {
+            pte_flags
         }
     }
     pub fn map_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
         let ppn: PhysPageNum;
+        let mut page = None;
         match self.map_type {
-            MapType::Identical => {
-                ppn = PhysPageNum(vpn.0);
+            MapType::Linear => {
+                ppn = VirtAddr::from(vpn).kernel_pa().floor();
             }
-            MapType::Framed => {
+            MapType::Framed | MapType::Lazy => {
                 let frame = frame_alloc().unwrap();
                 ppn = frame.ppn;
-                self.data_frames.insert(vpn, frame);
+                if let Some(backing) = &self.backing {
+                    let start = (vpn.0 - self.vpn_range.get_start().0) * PAGE_SIZE;
+                    if start < backing.len {
+                        let len = (backing.len - start).min(PAGE_SIZE);
+                        backing
+                            .inode
+                            .read_at(backing.offset + start, &mut ppn.get_bytes_array()[..len]);
+                    }
+                }
+                let new_page = Arc::new(Page::new(frame));
+                self.data_frames.insert(vpn, Arc::clone(&new_page));
+                page = Some(new_page);
             }
         }
-        let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
-        page_table.map(vpn, ppn, pte_flags);
+        page_table.map(vpn, ppn, self.pte_flags());
+        // the page can be found through its PTE only once it is mapped
+        if let (true, Some(page)) = (self.swappable(), page) {
+            page.set_owner(Some((page_table.token(), vpn)));
+        }
+    }
+    /// Map a page that is in `data_frames` but not in `page_table`,
+    /// reading it back from swap if it was swapped out.
+    pub fn map_resident(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
+        let page = &self.data_frames[&vpn];
+        let ppn = page.swap_in();
+        if Arc::strong_count(page) == 1 || self.shared {
+            page_table.map(vpn, ppn, self.pte_flags());
+            if self.swappable() {
+                page.set_owner(Some((page_table.token(), vpn)));
+            }
+        } else {
+            // still shared copy-on-write
+            page_table.map(vpn, ppn, self.pte_flags() - PTEFlags::W);
+        }
     }
     pub fn unmap_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
-        if self.map_type == MapType::Framed {
-            self.data_frames.remove(&vpn);
+        match self.map_type {
+            MapType::Framed => {
+                self.data_frames.remove(&vpn);
+            }
+            // a lazy page that was never touched is not in the page table
+            MapType::Lazy => {
+                if self.data_frames.remove(&vpn).is_none() {
+                    return;
+                }
+            }
+            MapType::Linear => {}
         }
+        // a page in swap is not in the page table either
+        if page_table
+            .translate(vpn)
+            .map_or(false, |pte| pte.is_valid())
+        {
+            page_table.unmap(vpn);
+        }
+    }
+    /// Make `vpn` writable again, copying its frame only if another space shares it.
+    pub fn copy_on_write(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) -> bool {
+        if !self.map_perm.contains(MapPermission::W) {
+            return false;
+        }
+        let swappable = self.swappable();
+        let pte_flags = self.pte_flags();
+        let page = match self.data_frames.get_mut(&vpn) {
+            Some(page) => page,
+            None => return false,
+        };
+        if Arc::strong_count(page) > 1 {
+            let new_frame = frame_alloc().unwrap();
+            new_frame
+                .ppn
+                .get_bytes_array()
+                .copy_from_slice(page.ppn().unwrap().get_bytes_array());
+            *page = Arc::new(Page::new(new_frame));
+        }
+        let ppn = page.ppn().unwrap();
         page_table.unmap(vpn);
+        page_table.map(vpn, ppn, pte_flags);
+        // no other space maps the page now
+        if swappable {
+            page.set_owner(Some((page_table.token(), vpn)));
+        }
+        true
+    }
+    /// Split a linear area into the largest pages aligned to their sizes,
+    /// with their levels
+    fn linear_pages(&self) -> Vec<(VirtPageNum, usize)> {
+        let mut pages = Vec::new();
+        let mut vpn = self.vpn_range.get_start().0;
+        let end = self.vpn_range.get_end().0;
+        while vpn < end {
+            let level = (0..=2)
+                .rev()
+                .find(|&level| vpn % level_pages(level) == 0 && vpn + level_pages(level) <= end)
+                .unwrap();
+            pages.push((VirtPageNum(vpn), level));
+            vpn += level_pages(level);
+        }
+        pages
     }
     pub fn map(&mut self, page_table: &mut PageTable) {
-        for vpn in self.vpn_range {
-            self.map_one(page_table, vpn);
+        match self.map_type {
+            // lazy pages are mapped on first touch
+            MapType::Lazy => {}
+            MapType::Linear => {
+                for (vpn, level) in self.linear_pages() {
+                    let ppn = VirtAddr::from(vpn).kernel_pa().floor();
+                    page_table.map_huge(vpn, ppn, level, self.pte_flags());
+                }
+            }
+            MapType::Framed => {
+                for vpn in self.vpn_range {
+                    self.map_one(page_table, vpn);
+                }
+            }
         }
     }
     pub fn unmap(&mut self, page_table: &mut PageTable) {
+        if self.map_type == MapType::Linear {
+            for (vpn, _) in self.linear_pages() {
+                page_table.unmap(vpn);
+            }
+            return;
+        }
         for vpn in self.vpn_range {
             self.unmap_one(page_table, vpn);
         }
     }
+    pub fn shrink_to(&mut self, page_table: &mut PageTable, new_end: VirtPageNum) {
+        for vpn in VPNRange::new(new_end, self.vpn_range.get_end()) {
+            self.unmap_one(page_table, vpn)
+        }
+        self.vpn_range = VPNRange::new(self.vpn_range.get_start(), new_end);
+    }
+    pub fn append_to(&mut self, page_table: &mut PageTable, new_end: VirtPageNum) {
+        // lazy pages are mapped on first touch
+        if self.map_type != MapType::Lazy {
+            for vpn in VPNRange::new(self.vpn_range.get_end(), new_end) {
+                self.map_one(page_table, vpn)
+            }
+        }
+        self.vpn_range = VPNRange::new(self.vpn_range.get_start(), new_end);
+    }
     /// data: start-aligned but maybe with shorter length
     /// assume that all frames were cleared before
     pub fn copy_data(&mut self, page_table: &PageTable, data: &[u8]) {
@@ -361,11 +778,24 @@ impl MapArea {
     }
 }
 
+/// Shared pages also reach the file when the area goes away on exit or exec
+impl Drop for MapArea {
+    fn drop(&mut self) {
+        self.write_back(self.vpn_range.get_start(), self.vpn_range.get_end());
+        // another hart evicting a page must be done with the page table before it is freed
+        for page in self.data_frames.values() {
+            page.set_owner(None);
+        }
+    }
+}
+
 #[derive(Copy, Clone, PartialEq, Debug)]
-/// map type for memory set: identical or framed
+/// map type for memory set: linear, framed, or framed on first touch
 pub enum MapType {
-    Identical,
+    /// the linear mapping of physical memory at `PHYS_VIRT_OFFSET`
+    Linear,
     Framed,
+    Lazy,
 }
 
 bitflags! {
@@ -404,5 +834,15 @@ pub fn remap_test() {
         .translate(mid_data.floor())
         .unwrap()
         .executable(),);
+    // physical memory is mostly mapped with huge pages
+    let ekernel_pa = VirtAddr::from(ekernel as usize).kernel_pa();
+    let mid_memory = PhysAddr::from((ekernel_pa.0 + MEMORY_END) / 2 + 0x123);
+    assert_eq!(
+        kernel_space
+            .page_table
+            .translate_va(mid_memory.kernel_va())
+            .unwrap(),
+        mid_memory
+    );
     println!("remap_test passed!");
 }
@@ -10,17 +10,21 @@ mod frame_allocator;
 mod heap_allocator;
 mod memory_set;
 mod page_table;
+mod swap;
 
 use address::VPNRange;
 pub use address::{PhysAddr, PhysPageNum, StepByOne, VirtAddr, VirtPageNum};
-pub use frame_allocator::{frame_alloc, frame_dealloc, FrameTracker};
+pub use frame_allocator::{
+    frame_alloc, frame_alloc_contiguous, frame_dealloc, frame_stats, FrameStats, FrameTracker,
+};
 pub use memory_set::remap_test;
-pub use memory_set::{kernel_token, MapPermission, MemorySet, KERNEL_SPACE};
-use page_table::PTEFlags;
+pub use memory_set::{kernel_token, MapBacking, MapPermission, MemorySet, KERNEL_SPACE};
 pub use page_table::{
-    translated_byte_buffer, translated_ref, translated_refmut, translated_str, PageTable,
+    copy_from_user, copy_to_user, strncpy_from_user, translated_byte_buffer, PageTable,
     PageTableEntry, UserBuffer, UserBufferIterator,
 };
+use page_table::{level_pages, PTEFlags};
+pub use swap::set_running_token;
 /// initiate heap allocator, frame allocator and kernel space
 pub fn init() {
     heap_allocator::init_heap();
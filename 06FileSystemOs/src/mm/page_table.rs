@@ -1,9 +1,14 @@
 //! Implementation of [`PageTableEntry`] and [`PageTable`].
+use super::swap::{pin_frame, unpin_frame};
 use super::{frame_alloc, FrameTracker, PhysAddr, PhysPageNum, StepByOne, VirtAddr, VirtPageNum};
+use crate::config::USER_SPACE_END;
+use crate::syscall::Errno;
+use crate::task::current_page_fault;
 use alloc::string::String;
 use alloc::vec;
 use alloc::vec::Vec;
 use bitflags::*;
+use core::mem::{size_of, MaybeUninit};
 
 bitflags! {
     pub struct PTEFlags: u8 {
@@ -61,6 +66,18 @@ impl PageTableEntry {
     pub fn executable(&self) -> bool {
         (self.flags() & PTEFlags::X) != PTEFlags::empty()
     }
+    ///Check PTE maps a page rather than points to the next level table
+    pub fn is_leaf(&self) -> bool {
+        self.is_valid()
+            && self
+                .flags()
+                .intersects(PTEFlags::R | PTEFlags::W | PTEFlags::X)
+    }
+}
+
+/// Number of 4KiB pages in a page of `level`
+pub fn level_pages(level: usize) -> usize {
+    1 << (9 * level)
 }
 ///Record root ppn and has the same lifetime as 1 and 2 level `PageTableEntry`
 pub struct PageTable {
@@ -85,14 +102,14 @@ impl PageTable {
             frames: Vec::new(),
         }
     }
-    /// Find phsical address by virtual address, create a frame if not exist
-    fn find_pte_create(&mut self, vpn: VirtPageNum) -> Option<&mut PageTableEntry> {
+    /// Find the PTE of `vpn` at `level`, create frames for the tables above if not exist
+    fn find_pte_create(&mut self, vpn: VirtPageNum, level: usize) -> Option<&mut PageTableEntry> {
         let idxs = vpn.indexes();
         let mut ppn = self.root_ppn;
         let mut result: Option<&mut PageTableEntry> = None;
         for (i, idx) in idxs.iter().enumerate() {
             let pte = &mut ppn.get_pte_array()[*idx];
-            if i == 2 {
+            if i == 2 - level {
                 result = Some(pte);
                 break;
             }
@@ -101,49 +118,82 @@ impl PageTable {
                 *pte = PageTableEntry::new(frame.ppn, PTEFlags::V);
                 self.frames.push(frame);
             }
+            assert!(!pte.is_leaf(), "vpn {:?} is in a huge page", vpn);
             ppn = pte.ppn();
         }
         result
     }
-    /// Find phsical address by virtual address
-    fn find_pte(&self, vpn: VirtPageNum) -> Option<&mut PageTableEntry> {
+    /// Find the leaf PTE mapping `vpn` and its level: 0 for a 4KiB page,
+    /// 1 for a 2MiB page and 2 for a 1GiB page.
+    /// The PTE of a 4KiB page is returned even if it is invalid.
+    fn find_leaf(&self, vpn: VirtPageNum) -> Option<(&mut PageTableEntry, usize)> {
         let idxs = vpn.indexes();
         let mut ppn = self.root_ppn;
-        let mut result: Option<&mut PageTableEntry> = None;
         for (i, idx) in idxs.iter().enumerate() {
             let pte = &mut ppn.get_pte_array()[*idx];
-            if i == 2 {
-                result = Some(pte);
-                break;
+            if i == 2 || pte.is_leaf() {
+                return Some((pte, 2 - i));
             }
             if !pte.is_valid() {
                 return None;
             }
             ppn = pte.ppn();
         }
-        result
+        unreachable!()
+    }
+    /// Find the PTE of the 4KiB page `vpn`, `None` if it is in a huge page
+    pub fn find_pte(&self, vpn: VirtPageNum) -> Option<&mut PageTableEntry> {
+        match self.find_leaf(vpn) {
+            Some((pte, 0)) => Some(pte),
+            _ => None,
+        }
     }
     #[allow(unused)]
     /// Create a mapping form `vpn` to `ppn`
     pub fn map(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, flags: PTEFlags) {
-        let pte = self.find_pte_create(vpn).unwrap();
+        self.map_huge(vpn, ppn, 0, flags);
+    }
+    /// Create a mapping form `vpn` to `ppn` with a page of `level`,
+    /// both of which must be aligned to the page
+    pub fn map_huge(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, level: usize, flags: PTEFlags) {
+        let pages = level_pages(level);
+        assert!(
+            vpn.0 % pages == 0 && ppn.0 % pages == 0,
+            "vpn {:?} or ppn {:?} is not aligned to a level {} page",
+            vpn,
+            ppn,
+            level
+        );
+        let pte = self.find_pte_create(vpn, level).unwrap();
         assert!(!pte.is_valid(), "vpn {:?} is mapped before mapping", vpn);
         *pte = PageTableEntry::new(ppn, flags | PTEFlags::V);
     }
     #[allow(unused)]
-    /// Delete a mapping form `vpn`
+    /// Delete a mapping form `vpn`, which must be the first page of a huge page
     pub fn unmap(&mut self, vpn: VirtPageNum) {
-        let pte = self.find_pte(vpn).unwrap();
+        let (pte, level) = self.find_leaf(vpn).unwrap();
         assert!(pte.is_valid(), "vpn {:?} is invalid before unmapping", vpn);
+        assert!(
+            vpn.0 % level_pages(level) == 0,
+            "vpn {:?} is in the middle of a huge page",
+            vpn
+        );
         *pte = PageTableEntry::empty();
     }
-    /// Translate `VirtPageNum` to `PageTableEntry`
+    /// Translate `VirtPageNum` to `PageTableEntry`,
+    /// as if `vpn` were mapped with a 4KiB page
     pub fn translate(&self, vpn: VirtPageNum) -> Option<PageTableEntry> {
-        self.find_pte(vpn).map(|pte| *pte)
+        self.find_leaf(vpn).map(|(pte, level)| {
+            if level == 0 {
+                return *pte;
+            }
+            let offset = vpn.0 & (level_pages(level) - 1);
+            PageTableEntry::new(PhysPageNum(pte.ppn().0 + offset), pte.flags())
+        })
     }
     /// Translate `VirtAddr` to `PhysAddr`
     pub fn translate_va(&self, va: VirtAddr) -> Option<PhysAddr> {
-        self.find_pte(va.clone().floor()).map(|pte| {
+        self.translate(va.clone().floor()).map(|pte| {
             let aligned_pa: PhysAddr = pte.ppn().into();
             let offset = va.page_offset();
             let aligned_pa_usize: usize = aligned_pa.into();
@@ -155,11 +205,81 @@ impl PageTable {
         8usize << 60 | self.root_ppn.0
     }
 }
-/// Translate a pointer to a mutable u8 Vec through page table
-pub fn translated_byte_buffer(token: usize, ptr: *const u8, len: usize) -> Vec<&'static mut [u8]> {
+/// The kernel accesses user pages through their physical addresses without faults,
+/// so map a lazy page, and copy a page shared copy-on-write before writing.
+fn prepare_access(page_table: &PageTable, vpn: VirtPageNum, write: bool) {
+    let va: VirtAddr = vpn.into();
+    match page_table.translate(vpn) {
+        Some(pte) if pte.is_valid() => {
+            if write && !pte.writable() && pte.flags().contains(PTEFlags::U) {
+                current_page_fault(va.into(), true);
+            }
+        }
+        _ => {
+            if current_page_fault(va.into(), false) {
+                prepare_access(page_table, vpn, write);
+            }
+        }
+    }
+}
+/// Check that `[start, start + len)` is in user space, and return its end
+fn user_range(start: usize, len: usize) -> Result<usize, Errno> {
+    match start.checked_add(len) {
+        Some(end) if end <= USER_SPACE_END => Ok(end),
+        _ => Err(Errno::EFAULT),
+    }
+}
+/// Bring in `vpn` for the kernel, and check that user mode may read it,
+/// or write it if `write`
+fn check_user_page(
+    page_table: &PageTable,
+    vpn: VirtPageNum,
+    write: bool,
+) -> Result<PhysPageNum, Errno> {
+    prepare_access(page_table, vpn, write);
+    match page_table.translate(vpn) {
+        Some(pte)
+            if pte.is_valid()
+                && pte.flags().contains(PTEFlags::U)
+                && pte.readable()
+                && (!write || pte.writable()) =>
+        {
+            Ok(pte.ppn())
+        }
+        _ => Err(Errno::EFAULT),
+    }
+}
+/// Translate the user buffer `[ptr, ptr + len)` to slices of kernel memory,
+/// checking that user mode may read it, or write it if `write`
+pub fn translated_byte_buffer(
+    token: usize,
+    ptr: *const u8,
+    len: usize,
+    write: bool,
+) -> Result<Vec<&'static mut [u8]>, Errno> {
     let page_table = PageTable::from_token(token);
     let mut start = ptr as usize;
-    let end = start + len;
+    let end = user_range(start, len)?;
+    // bringing in a page may evict another one, so bring in all of them first
+    let end_vpn = VirtAddr::from(end).ceil();
+    loop {
+        let mut vpn = VirtAddr::from(start).floor();
+        while vpn < end_vpn {
+            check_user_page(&page_table, vpn, write)?;
+            vpn.step();
+        }
+        let mut vpn = VirtAddr::from(start).floor();
+        while vpn < end_vpn
+            && page_table
+                .translate(vpn)
+                .map_or(false, |pte| pte.is_valid())
+        {
+            vpn.step();
+        }
+        if vpn == end_vpn {
+            break;
+        }
+    }
     let mut v = Vec::new();
     while start < end {
         let start_va = VirtAddr::from(start);
@@ -175,56 +295,93 @@ pub fn translated_byte_buffer(token: usize, ptr: *const u8, len: usize) -> Vec<&
         }
         start = end_va.into();
     }
-    v
+    Ok(v)
 }
 
-/// Translate a pointer to a mutable u8 Vec end with `\0` through page table to a `String`
-pub fn translated_str(token: usize, ptr: *const u8) -> String {
+/// Copy a NUL-terminated string of at most `max_len` bytes from user memory at `src`
+pub fn strncpy_from_user(token: usize, src: *const u8, max_len: usize) -> Result<String, Errno> {
     let page_table = PageTable::from_token(token);
-    let mut string = String::new();
-    let mut va = ptr as usize;
+    let mut bytes = Vec::new();
+    let mut va = src as usize;
+    let mut ppn = PhysPageNum(0);
     loop {
-        let ch: u8 = *(page_table
-            .translate_va(VirtAddr::from(va))
-            .unwrap()
-            .get_mut());
+        if bytes.len() == max_len {
+            return Err(Errno::ENAMETOOLONG);
+        }
+        user_range(va, 1)?;
+        let offset = VirtAddr::from(va).page_offset();
+        if va == src as usize || offset == 0 {
+            ppn = check_user_page(&page_table, VirtAddr::from(va).floor(), false)?;
+        }
+        let ch = ppn.get_bytes_array()[offset];
         if ch == 0 {
             break;
         }
-        string.push(ch as char);
+        bytes.push(ch);
         va += 1;
     }
-    string
+    Ok(bytes.iter().map(|&ch| ch as char).collect())
 }
 
-#[allow(unused)]
-///Translate a generic through page table and return a reference
-pub fn translated_ref<T>(token: usize, ptr: *const T) -> &'static T {
-    let page_table = PageTable::from_token(token);
-    page_table
-        .translate_va(VirtAddr::from(ptr as usize))
-        .unwrap()
-        .get_ref()
+/// Copy a `T` from user memory at `src`
+pub fn copy_from_user<T: Copy>(token: usize, src: *const T) -> Result<T, Errno> {
+    let mut value = MaybeUninit::<T>::uninit();
+    let bytes =
+        unsafe { core::slice::from_raw_parts_mut(value.as_mut_ptr() as *mut u8, size_of::<T>()) };
+    let mut offset = 0;
+    // the value may cross a page boundary in user space
+    for buffer in translated_byte_buffer(token, src as *const u8, size_of::<T>(), false)? {
+        bytes[offset..offset + buffer.len()].copy_from_slice(buffer);
+        offset += buffer.len();
+    }
+    Ok(unsafe { value.assume_init() })
 }
-///Translate a generic through page table and return a mutable reference
-pub fn translated_refmut<T>(token: usize, ptr: *mut T) -> &'static mut T {
-    let page_table = PageTable::from_token(token);
-    let va = ptr as usize;
-    page_table
-        .translate_va(VirtAddr::from(va))
-        .unwrap()
-        .get_mut()
+
+/// Copy `value` to user memory at `dst`
+pub fn copy_to_user<T>(token: usize, dst: *mut T, value: &T) -> Result<(), Errno> {
+    let bytes =
+        unsafe { core::slice::from_raw_parts(value as *const T as *const u8, size_of::<T>()) };
+    let mut offset = 0;
+    for buffer in translated_byte_buffer(token, dst as *const u8, size_of::<T>(), true)? {
+        buffer.copy_from_slice(&bytes[offset..offset + buffer.len()]);
+        offset += buffer.len();
+    }
+    Ok(())
 }
 ///Array of u8 slice that user communicate with os
 pub struct UserBuffer {
     ///U8 vec
     pub buffers: Vec<&'static mut [u8]>,
+    /// the frames can not be swapped out while a blocked task holds them
+    pinned: PinnedFrames,
+}
+
+/// Frames pinned in memory until dropped
+struct PinnedFrames(Vec<PhysPageNum>);
+
+impl Drop for PinnedFrames {
+    fn drop(&mut self) {
+        for ppn in self.0.iter() {
+            unpin_frame(*ppn);
+        }
+    }
 }
 
 impl UserBuffer {
     ///Create a `UserBuffer` by parameter
     pub fn new(buffers: Vec<&'static mut [u8]>) -> Self {
-        Self { buffers }
+        // the buffers are in the linear mapping of physical memory
+        let pinned: Vec<PhysPageNum> = buffers
+            .iter()
+            .map(|buffer| VirtAddr::from(buffer.as_ptr() as usize).kernel_pa().floor())
+            .collect();
+        for ppn in pinned.iter() {
+            pin_frame(*ppn);
+        }
+        Self {
+            buffers,
+            pinned: PinnedFrames(pinned),
+        }
     }
     ///Length of `UserBuffer`
     pub fn len(&self) -> usize {
@@ -244,6 +401,7 @@ impl IntoIterator for UserBuffer {
             buffers: self.buffers,
             current_buffer: 0,
             current_idx: 0,
+            _pinned: self.pinned,
         }
     }
 }
@@ -252,6 +410,7 @@ pub struct UserBufferIterator {
     buffers: Vec<&'static mut [u8]>,
     current_buffer: usize,
     current_idx: usize,
+    _pinned: PinnedFrames,
 }
 
 impl Iterator for UserBufferIterator {
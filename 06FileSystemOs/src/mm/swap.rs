@@ -0,0 +1,272 @@
+//! Swapping user pages out to a reserved area of the block device.
+//!
+//! User pages are [`Page`]s shared between the map areas that hold them.
+//! When [`frame_alloc`] runs out of frames, [`swap_out_one`] runs a clock
+//! over the pages that may be evicted: a page whose accessed bit is set gets
+//! a second chance, otherwise it is written to a swap slot and its PTE is
+//! cleared. The page is read back in on the next page fault.
+//!
+//! Only pages that a single address space owns are evicted, since there is
+//! no way to find every PTE mapping a page shared copy-on-write.
+use super::{frame_alloc, FrameTracker, PTEFlags, PageTable, PageTableEntry};
+use super::{PhysPageNum, VirtPageNum};
+use crate::config::{MAX_HARTS, PAGE_SIZE, SWAP_PAGES, SWAP_START_BLOCK};
+use crate::drivers::BLOCK_DEVICE;
+use crate::sync::SpinLock;
+use crate::task::hart_id;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use easy_fs::BLOCK_SZ;
+use lazy_static::*;
+
+/// A page of user memory, in a frame or in a swap slot
+pub struct Page {
+    inner: SpinLock<PageInner>,
+}
+
+struct PageInner {
+    state: PageState,
+    /// token of the only page table mapping the page and where,
+    /// `None` if the page must not be evicted
+    owner: Option<(usize, VirtPageNum)>,
+    /// whether the page is queued in `SWAP_CLOCK`, so that it is queued at most once
+    in_clock: bool,
+}
+
+enum PageState {
+    Resident(FrameTracker),
+    Swapped(usize),
+}
+
+impl Page {
+    /// Create a resident page that is not evicted until it gets an owner
+    pub fn new(frame: FrameTracker) -> Self {
+        Self {
+            inner: SpinLock::new(PageInner {
+                state: PageState::Resident(frame),
+                owner: None,
+                in_clock: false,
+            }),
+        }
+    }
+    /// The frame of the page, `None` if it is swapped out
+    pub fn ppn(&self) -> Option<PhysPageNum> {
+        match &self.inner.exclusive_access().state {
+            PageState::Resident(frame) => Some(frame.ppn),
+            PageState::Swapped(_) => None,
+        }
+    }
+    /// Read the page back from swap if it was evicted, and return its frame
+    pub fn swap_in(&self) -> PhysPageNum {
+        if let Some(ppn) = self.ppn() {
+            return ppn;
+        }
+        // allocating may evict other pages, so do it before borrowing this one
+        let frame = frame_alloc().unwrap();
+        let ppn = frame.ppn;
+        let mut inner = self.inner.exclusive_access();
+        match inner.state {
+            PageState::Swapped(slot) => {
+                read_slot(slot, ppn);
+                SWAP_SLOTS.exclusive_access().dealloc(slot);
+            }
+            // another hart read it back in the meantime
+            PageState::Resident(ref frame) => return frame.ppn,
+        }
+        inner.state = PageState::Resident(frame);
+        ppn
+    }
+    /// Let the page be evicted through its PTE at `vpn` of the page table `token`,
+    /// or keep it resident if `owner` is `None`
+    pub fn set_owner(self: &Arc<Self>, owner: Option<(usize, VirtPageNum)>) {
+        let mut inner = self.inner.exclusive_access();
+        inner.owner = owner;
+        let enqueue = owner.is_some() && !inner.in_clock;
+        inner.in_clock |= enqueue;
+        // the clock takes page locks while it is held
+        drop(inner);
+        if enqueue {
+            SWAP_CLOCK.exclusive_access().push(Arc::downgrade(self));
+        }
+    }
+}
+
+impl Drop for Page {
+    fn drop(&mut self) {
+        if let PageState::Swapped(slot) = self.inner.exclusive_access().state {
+            SWAP_SLOTS.exclusive_access().dealloc(slot);
+        }
+    }
+}
+
+/// Pages that may be evicted, in clock order. A page leaves the queue when the
+/// clock passes over it while it may not be evicted, or when it is dropped.
+struct SwapClock {
+    pages: VecDeque<Weak<Page>>,
+    /// length of `pages` after dead pages were last swept out
+    swept_len: usize,
+}
+
+impl SwapClock {
+    /// Queue a page, sweeping out dropped pages once they may make up half of
+    /// the queue, so that the queue stays in proportion to the live pages
+    /// even if the clock never runs.
+    fn push(&mut self, page: Weak<Page>) {
+        if self.pages.len() >= 2 * self.swept_len.max(64) {
+            self.pages.retain(|page| page.strong_count() > 0);
+            self.swept_len = self.pages.len();
+        }
+        self.pages.push_back(page);
+    }
+}
+
+/// an allocator of swap slots, handing out recycled slots first
+struct SwapSlotAllocator {
+    current: usize,
+    end: usize,
+    recycled: Vec<usize>,
+}
+
+impl SwapSlotAllocator {
+    fn alloc(&mut self) -> Option<usize> {
+        if let Some(slot) = self.recycled.pop() {
+            Some(slot)
+        } else if self.current == self.end {
+            None
+        } else {
+            self.current += 1;
+            Some(self.current - 1)
+        }
+    }
+    fn dealloc(&mut self, slot: usize) {
+        // validity check
+        if slot >= self.current || self.recycled.iter().any(|&v| v == slot) {
+            panic!("Swap slot {} has not been allocated!", slot);
+        }
+        self.recycled.push(slot);
+    }
+}
+
+lazy_static! {
+    static ref SWAP_SLOTS: SpinLock<SwapSlotAllocator> = SpinLock::new(SwapSlotAllocator {
+            current: 0,
+            end: SWAP_PAGES,
+            recycled: Vec::new(),
+        });
+    static ref SWAP_CLOCK: SpinLock<SwapClock> = SpinLock::new(SwapClock {
+        pages: VecDeque::new(),
+        swept_len: 0,
+    });
+    /// user page table of the task each hart runs, 0 if it runs no task.
+    /// The TLB of a hart may hold PTEs of its token, so their pages are not evicted.
+    static ref RUNNING_TOKENS: SpinLock<[usize; MAX_HARTS]> = SpinLock::new([0; MAX_HARTS]);
+    /// frames the kernel is reading or writing for a user, with pin counts
+    static ref PINNED_FRAMES: SpinLock<BTreeMap<PhysPageNum, usize>> =
+        SpinLock::new(BTreeMap::new());
+}
+
+const BLOCKS_PER_PAGE: usize = PAGE_SIZE / BLOCK_SZ;
+
+fn write_slot(slot: usize, ppn: PhysPageNum) {
+    let bytes = ppn.get_bytes_array();
+    for (i, block) in bytes.chunks(BLOCK_SZ).enumerate() {
+        BLOCK_DEVICE.write_block(SWAP_START_BLOCK + slot * BLOCKS_PER_PAGE + i, block);
+    }
+}
+
+fn read_slot(slot: usize, ppn: PhysPageNum) {
+    let bytes = ppn.get_bytes_array();
+    for (i, block) in bytes.chunks_mut(BLOCK_SZ).enumerate() {
+        BLOCK_DEVICE.read_block(SWAP_START_BLOCK + slot * BLOCKS_PER_PAGE + i, block);
+    }
+}
+
+/// Evict one page to swap, return false if no page can be evicted.
+/// Pages of the tokens other harts run are skipped, as their TLBs may map them.
+/// Pages of the current hart's token may be evicted, since its TLB holds no user
+/// PTEs in the kernel and is flushed by `__restore` when returning to user mode.
+pub fn swap_out_one() -> bool {
+    // no hart may start running a token until the PTE of its victim is cleared
+    let running = RUNNING_TOKENS.exclusive_access();
+    let hart = hart_id();
+    let mut clock = SWAP_CLOCK.exclusive_access();
+    // a page passed over once has its accessed bit cleared
+    let mut steps = clock.pages.len() * 2;
+    while steps > 0 {
+        steps -= 1;
+        let page = match clock.pages.pop_front().and_then(|page| page.upgrade()) {
+            Some(page) => page,
+            None => continue,
+        };
+        let mut inner = page.inner.exclusive_access();
+        // pages that got shared or swapped leave the clock until they get an owner again
+        inner.in_clock = false;
+        let (token, vpn) = match inner.owner {
+            Some(owner) if Arc::strong_count(&page) == 2 => owner,
+            _ => continue,
+        };
+        let ppn = match &inner.state {
+            PageState::Resident(frame) => frame.ppn,
+            PageState::Swapped(_) => continue,
+        };
+        let page_table = PageTable::from_token(token);
+        let pte = match page_table.find_pte(vpn) {
+            Some(pte) if pte.is_valid() && pte.ppn() == ppn => pte,
+            _ => continue,
+        };
+        // the TLB of the hart running the page table may still map the page
+        let busy = (0..MAX_HARTS).any(|id| id != hart && running[id] == token);
+        if busy || pte.flags().contains(PTEFlags::A) || is_pinned(ppn) {
+            if !busy {
+                pte.bits &= !(PTEFlags::A.bits as usize);
+            }
+            inner.in_clock = true;
+            drop(inner);
+            clock.pages.push_back(Arc::downgrade(&page));
+            continue;
+        }
+        let slot = match SWAP_SLOTS.exclusive_access().alloc() {
+            Some(slot) => slot,
+            None => {
+                inner.in_clock = true;
+                drop(inner);
+                clock.pages.push_back(Arc::downgrade(&page));
+                return false;
+            }
+        };
+        *pte = PageTableEntry::empty();
+        // other harts wait on the disk no longer than it takes to fault on the
+        // page, which waits for the page lock in `swap_in`
+        drop(clock);
+        drop(running);
+        write_slot(slot, ppn);
+        // drop the frame
+        inner.state = PageState::Swapped(slot);
+        return true;
+    }
+    false
+}
+
+/// Record that the current hart is about to run the user page table `token`, or none if 0
+pub fn set_running_token(token: usize) {
+    RUNNING_TOKENS.exclusive_access()[hart_id()] = token;
+}
+
+/// Keep the frame `ppn` resident until `unpin_frame`
+pub fn pin_frame(ppn: PhysPageNum) {
+    *PINNED_FRAMES.exclusive_access().entry(ppn).or_insert(0) += 1;
+}
+
+pub fn unpin_frame(ppn: PhysPageNum) {
+    let mut pinned = PINNED_FRAMES.exclusive_access();
+    let count = pinned.get_mut(&ppn).unwrap();
+    *count -= 1;
+    if *count == 0 {
+        pinned.remove(&ppn);
+    }
+}
+
+fn is_pinned(ppn: PhysPageNum) -> bool {
+    PINNED_FRAMES.exclusive_access().contains_key(&ppn)
+}
@@ -28,3 +28,9 @@ pub fn shutdown(failure: bool) -> ! {
     }
     unreachable!()
 }
+
+/// use sbi call to start hart `hartid` at physical address `start_addr` with `opaque` in a1,
+/// return whether the hart is starting
+pub fn hart_start(hartid: usize, start_addr: usize, opaque: usize) -> bool {
+    sbi_rt::hart_start(hartid, start_addr, opaque).error == 0
+}
@@ -1,4 +1,4 @@
 //! Synchronization and interior mutability primitives
-mod up;
+mod spin;
 
-pub use up::UPSafeCell;
+pub use spin::{SpinLock, SpinLockGuard};
@@ -1,71 +1,270 @@
 //! File and filesystem-related syscalls
-use crate::fs::{open_file, OpenFlags};
-use crate::mm::{translated_byte_buffer, translated_str, UserBuffer};
+use super::{Errno, SyscallResult, PATH_MAX};
+use crate::config::MAX_FD;
+use crate::fs::{link, make_pipe, mkdir, open_file, rmdir, unlink, OpenFlags, SeekFrom, Stat};
+use crate::mm::{copy_to_user, strncpy_from_user, translated_byte_buffer, UserBuffer};
 use crate::task::{current_task, current_user_token};
 
-pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
+/// Special value of `dirfd`: resolve relative paths from the current directory,
+/// which is always the root directory for now
+const AT_FDCWD: isize = -100;
+/// `whence` of `lseek`: set the offset to `offset`
+const SEEK_SET: usize = 0;
+/// `whence` of `lseek`: set the offset to current offset plus `offset`
+const SEEK_CUR: usize = 1;
+/// `whence` of `lseek`: set the offset to file size plus `offset`
+const SEEK_END: usize = 2;
+/// Flag of `unlinkat`: remove a directory instead of a file
+const AT_REMOVEDIR: u32 = 0x200;
+
+pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> SyscallResult {
     let token = current_user_token();
     let task = current_task().unwrap();
     let inner = task.inner_exclusive_access();
     if fd >= inner.fd_table.len() {
-        return -1;
+        return Err(Errno::EBADF);
     }
     if let Some(file) = &inner.fd_table[fd] {
         if !file.writable() {
-            return -1;
+            return Err(Errno::EBADF);
         }
         let file = file.clone();
         // release current task TCB manually to avoid multi-borrow
         drop(inner);
-        file.write(UserBuffer::new(translated_byte_buffer(token, buf, len))) as isize
+        let buffers = translated_byte_buffer(token, buf, len, false)?;
+        Ok(file.write(UserBuffer::new(buffers))? as isize)
     } else {
-        -1
+        Err(Errno::EBADF)
     }
 }
 
-pub fn sys_read(fd: usize, buf: *const u8, len: usize) -> isize {
+pub fn sys_read(fd: usize, buf: *const u8, len: usize) -> SyscallResult {
     let token = current_user_token();
     let task = current_task().unwrap();
     let inner = task.inner_exclusive_access();
     if fd >= inner.fd_table.len() {
-        return -1;
+        return Err(Errno::EBADF);
     }
     if let Some(file) = &inner.fd_table[fd] {
         let file = file.clone();
         if !file.readable() {
-            return -1;
+            return Err(Errno::EBADF);
         }
         // release current task TCB manually to avoid multi-borrow
         drop(inner);
-        file.read(UserBuffer::new(translated_byte_buffer(token, buf, len))) as isize
+        let buffers = translated_byte_buffer(token, buf, len, true)?;
+        Ok(file.read(UserBuffer::new(buffers))? as isize)
     } else {
-        -1
+        Err(Errno::EBADF)
     }
 }
 
-pub fn sys_open(path: *const u8, flags: u32) -> isize {
+pub fn sys_open(path: *const u8, flags: u32) -> SyscallResult {
     let task = current_task().unwrap();
     let token = current_user_token();
-    let path = translated_str(token, path);
-    if let Some(inode) = open_file(path.as_str(), OpenFlags::from_bits(flags).unwrap()) {
-        let mut inner = task.inner_exclusive_access();
-        let fd = inner.alloc_fd();
-        inner.fd_table[fd] = Some(inode);
-        fd as isize
-    } else {
-        -1
-    }
+    let path = strncpy_from_user(token, path, PATH_MAX)?;
+    let flags = OpenFlags::from_bits(flags).ok_or(Errno::EINVAL)?;
+    let inode = open_file(path.as_str(), flags)?;
+    let mut inner = task.inner_exclusive_access();
+    let fd = inner.alloc_fd();
+    inner.fd_table[fd] = Some(inode);
+    Ok(fd as isize)
 }
 
-pub fn sys_close(fd: usize) -> isize {
+pub fn sys_close(fd: usize) -> SyscallResult {
     let task = current_task().unwrap();
     let mut inner = task.inner_exclusive_access();
     if fd >= inner.fd_table.len() {
-        return -1;
+        return Err(Errno::EBADF);
     }
     if inner.fd_table[fd].is_none() {
-        return -1;
+        return Err(Errno::EBADF);
     }
     inner.fd_table[fd].take();
-    0
+    Ok(0)
+}
+
+pub fn sys_pipe(pipe: *mut usize) -> SyscallResult {
+    let task = current_task().unwrap();
+    let token = current_user_token();
+    let mut inner = task.inner_exclusive_access();
+    let (pipe_read, pipe_write) = make_pipe();
+    let read_fd = inner.alloc_fd();
+    inner.fd_table[read_fd] = Some(pipe_read);
+    let write_fd = inner.alloc_fd();
+    inner.fd_table[write_fd] = Some(pipe_write);
+    // writing to user memory may have to handle a copy-on-write page
+    drop(inner);
+    if let Err(err) = copy_to_user(token, pipe as *mut [usize; 2], &[read_fd, write_fd]) {
+        let mut inner = task.inner_exclusive_access();
+        inner.fd_table[read_fd].take();
+        inner.fd_table[write_fd].take();
+        return Err(err);
+    }
+    Ok(0)
+}
+
+pub fn sys_dup(fd: usize) -> SyscallResult {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    if fd >= inner.fd_table.len() {
+        return Err(Errno::EBADF);
+    }
+    let file = match &inner.fd_table[fd] {
+        Some(file) => file.clone(),
+        None => return Err(Errno::EBADF),
+    };
+    let new_fd = inner.alloc_fd();
+    inner.fd_table[new_fd] = Some(file);
+    Ok(new_fd as isize)
+}
+
+/// Make `new_fd` refer to the same file as `old_fd`, closing the file
+/// `new_fd` referred to before. `flags` is reserved and must be 0,
+/// and `new_fd` must be below [`MAX_FD`].
+pub fn sys_dup3(old_fd: usize, new_fd: usize, flags: u32) -> SyscallResult {
+    if old_fd == new_fd || flags != 0 {
+        return Err(Errno::EINVAL);
+    }
+    // the fd table is grown up to `new_fd`
+    if new_fd >= MAX_FD {
+        return Err(Errno::EBADF);
+    }
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    if old_fd >= inner.fd_table.len() {
+        return Err(Errno::EBADF);
+    }
+    let file = match &inner.fd_table[old_fd] {
+        Some(file) => file.clone(),
+        None => return Err(Errno::EBADF),
+    };
+    if new_fd >= inner.fd_table.len() {
+        inner.fd_table.resize(new_fd + 1, None);
+    }
+    inner.fd_table[new_fd] = Some(file);
+    Ok(new_fd as isize)
+}
+
+pub fn sys_mkdirat(dirfd: isize, path: *const u8, _mode: u32) -> SyscallResult {
+    if dirfd != AT_FDCWD {
+        return Err(Errno::EBADF);
+    }
+    let token = current_user_token();
+    let path = strncpy_from_user(token, path, PATH_MAX)?;
+    mkdir(path.as_str())?;
+    Ok(0)
+}
+
+pub fn sys_unlinkat(dirfd: isize, path: *const u8, flags: u32) -> SyscallResult {
+    if dirfd != AT_FDCWD {
+        return Err(Errno::EBADF);
+    }
+    let token = current_user_token();
+    let path = strncpy_from_user(token, path, PATH_MAX)?;
+    if flags & AT_REMOVEDIR != 0 {
+        rmdir(path.as_str())?;
+    } else {
+        unlink(path.as_str())?;
+    }
+    Ok(0)
+}
+
+pub fn sys_linkat(
+    olddirfd: isize,
+    oldpath: *const u8,
+    newdirfd: isize,
+    newpath: *const u8,
+    _flags: u32,
+) -> SyscallResult {
+    if olddirfd != AT_FDCWD || newdirfd != AT_FDCWD {
+        return Err(Errno::EBADF);
+    }
+    let token = current_user_token();
+    let oldpath = strncpy_from_user(token, oldpath, PATH_MAX)?;
+    let newpath = strncpy_from_user(token, newpath, PATH_MAX)?;
+    link(oldpath.as_str(), newpath.as_str())?;
+    Ok(0)
+}
+
+pub fn sys_fstat(fd: usize, st: *mut Stat) -> SyscallResult {
+    let token = current_user_token();
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    if fd >= inner.fd_table.len() {
+        return Err(Errno::EBADF);
+    }
+    if let Some(file) = &inner.fd_table[fd] {
+        let file = file.clone();
+        // release current task TCB manually to avoid multi-borrow
+        drop(inner);
+        copy_to_user(token, st, &file.stat())?;
+        Ok(0)
+    } else {
+        Err(Errno::EBADF)
+    }
+}
+
+pub fn sys_lseek(fd: usize, offset: isize, whence: usize) -> SyscallResult {
+    let pos = match whence {
+        SEEK_SET if offset >= 0 => SeekFrom::Start(offset as usize),
+        SEEK_CUR => SeekFrom::Current(offset),
+        SEEK_END => SeekFrom::End(offset),
+        _ => return Err(Errno::EINVAL),
+    };
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    if fd >= inner.fd_table.len() {
+        return Err(Errno::EBADF);
+    }
+    if let Some(file) = &inner.fd_table[fd] {
+        let file = file.clone();
+        // release current task TCB manually to avoid multi-borrow
+        drop(inner);
+        Ok(file.seek(pos)? as isize)
+    } else {
+        Err(Errno::EBADF)
+    }
+}
+
+pub fn sys_pread64(fd: usize, buf: *const u8, len: usize, offset: usize) -> SyscallResult {
+    let token = current_user_token();
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    if fd >= inner.fd_table.len() {
+        return Err(Errno::EBADF);
+    }
+    if let Some(file) = &inner.fd_table[fd] {
+        let file = file.clone();
+        if !file.readable() {
+            return Err(Errno::EBADF);
+        }
+        // release current task TCB manually to avoid multi-borrow
+        drop(inner);
+        let buffers = translated_byte_buffer(token, buf, len, true)?;
+        Ok(file.read_at(offset, UserBuffer::new(buffers))? as isize)
+    } else {
+        Err(Errno::EBADF)
+    }
+}
+
+pub fn sys_pwrite64(fd: usize, buf: *const u8, len: usize, offset: usize) -> SyscallResult {
+    let token = current_user_token();
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    if fd >= inner.fd_table.len() {
+        return Err(Errno::EBADF);
+    }
+    if let Some(file) = &inner.fd_table[fd] {
+        if !file.writable() {
+            return Err(Errno::EBADF);
+        }
+        let file = file.clone();
+        // release current task TCB manually to avoid multi-borrow
+        drop(inner);
+        let buffers = translated_byte_buffer(token, buf, len, false)?;
+        Ok(file.write_at(offset, UserBuffer::new(buffers))? as isize)
+    } else {
+        Err(Errno::EBADF)
+    }
 }
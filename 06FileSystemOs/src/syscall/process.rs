@@ -1,31 +1,99 @@
-use crate::fs::{open_file, OpenFlags};
-use crate::mm::{translated_refmut, translated_str};
+use super::{Errno, SyscallResult, PATH_MAX};
+use crate::config::{PAGE_SIZE, USER_SPACE_END, USER_STACK_SIZE};
+use crate::fs::{open_file, File, OpenFlags, WriteAccess};
+use crate::mm::{
+    copy_from_user, copy_to_user, strncpy_from_user, MapBacking, MapPermission, VirtAddr,
+    VirtPageNum,
+};
 use crate::task::{
-    add_task, current_task, current_user_token, exit_current_and_run_next,
-    suspend_current_and_run_next,
+    add_task, add_timer, block_current_and_run_next, current_task, current_user_token,
+    exit_current_and_run_next, pid2task, suspend_current_and_run_next, SignalAction, SignalFlags,
+    BIG_STRIDE,
 };
-use crate::timer::get_time_ms;
+use crate::timer::{get_realtime_ns, get_time, get_time_ms, get_time_ns, TimeSpec};
+use alloc::string::String;
 use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::mem::size_of;
+
+bitflags! {
+    /// Protection of a mapping
+    struct MmapProt: u32 {
+        const READ = 1 << 0;
+        const WRITE = 1 << 1;
+        const EXEC = 1 << 2;
+    }
+}
+
+bitflags! {
+    /// Flags of `mmap`, exactly one of `SHARED` and `PRIVATE` must be given
+    struct MmapFlags: u32 {
+        const SHARED = 1 << 0;
+        const PRIVATE = 1 << 1;
+        const FIXED = 1 << 4;
+        const ANONYMOUS = 1 << 5;
+    }
+}
+
+/// `clock_id` of `clock_gettime`: wall-clock time since the Unix epoch
+const CLOCK_REALTIME: usize = 0;
+/// `clock_id` of `clock_gettime`: time since boot, which never jumps
+const CLOCK_MONOTONIC: usize = 1;
+
+/// Flag of `msync`: schedule the write-back and return
+const MS_ASYNC: u32 = 1;
+/// Flag of `msync`: invalidate other mappings of the file, nothing to do here
+const MS_INVALIDATE: u32 = 2;
+/// Flag of `msync`: write back before returning
+const MS_SYNC: u32 = 4;
 
 pub fn sys_exit(exit_code: i32) -> ! {
     exit_current_and_run_next(exit_code);
     panic!("Unreachable in sys_exit!");
 }
 
-pub fn sys_yield() -> isize {
+pub fn sys_yield() -> SyscallResult {
     suspend_current_and_run_next();
-    0
+    Ok(0)
 }
 
-pub fn sys_get_time() -> isize {
-    get_time_ms() as isize
+/// Block for the span `req`. The sleep is never interrupted, so `rem`, if not NULL, gets 0.
+/// Signals, even `SIGKILL`, do not wake the task: they are handled once the deadline passes.
+/// A span past the end of the clock sleeps forever.
+pub fn sys_nanosleep(req: *const TimeSpec, rem: *mut TimeSpec) -> SyscallResult {
+    let token = current_user_token();
+    let ticks = copy_from_user(token, req)?
+        .to_ticks()
+        .ok_or(Errno::EINVAL)?;
+    if ticks > 0 {
+        add_timer(get_time().saturating_add(ticks), current_task().unwrap());
+        block_current_and_run_next();
+    }
+    if !rem.is_null() {
+        copy_to_user(token, rem, &TimeSpec::default())?;
+    }
+    Ok(0)
 }
 
-pub fn sys_getpid() -> isize {
-    current_task().unwrap().pid.0 as isize
+pub fn sys_clock_gettime(clock_id: usize, tp: *mut TimeSpec) -> SyscallResult {
+    let ns = match clock_id {
+        CLOCK_REALTIME => get_realtime_ns(),
+        CLOCK_MONOTONIC => get_time_ns(),
+        _ => return Err(Errno::EINVAL),
+    };
+    copy_to_user(current_user_token(), tp, &TimeSpec::from_ns(ns))?;
+    Ok(0)
 }
 
-pub fn sys_fork() -> isize {
+pub fn sys_get_time() -> SyscallResult {
+    Ok(get_time_ms() as isize)
+}
+
+pub fn sys_getpid() -> SyscallResult {
+    Ok(current_task().unwrap().pid.0 as isize)
+}
+
+pub fn sys_fork() -> SyscallResult {
     let current_task = current_task().unwrap();
     let new_task = current_task.fork();
     let new_pid = new_task.pid.0;
@@ -36,55 +104,309 @@ pub fn sys_fork() -> isize {
     trap_cx.x[10] = 0;
     // add new task to scheduler
     add_task(new_task);
-    new_pid as isize
+    Ok(new_pid as isize)
+}
+
+/// Read a NULL-terminated array of string pointers from user space.
+/// A NULL `ptr` stands for an empty array. Each string with its pointer, and the
+/// terminating NULL, is taken from the stack `space` left, failing with `E2BIG`
+/// as soon as it runs out.
+fn copy_str_array_from_user(
+    token: usize,
+    mut ptr: *const usize,
+    space: &mut usize,
+) -> Result<Vec<String>, Errno> {
+    let mut strings = Vec::new();
+    *space = space.checked_sub(size_of::<usize>()).ok_or(Errno::E2BIG)?;
+    if ptr.is_null() {
+        return Ok(strings);
+    }
+    loop {
+        let str_ptr = copy_from_user(token, ptr)?;
+        if str_ptr == 0 {
+            break;
+        }
+        let string = match strncpy_from_user(token, str_ptr as *const u8, *space) {
+            Err(Errno::ENAMETOOLONG) => return Err(Errno::E2BIG),
+            string => string?,
+        };
+        *space = space
+            .checked_sub(string.len() + 1 + size_of::<usize>())
+            .ok_or(Errno::E2BIG)?;
+        strings.push(string);
+        unsafe {
+            ptr = ptr.add(1);
+        }
+    }
+    Ok(strings)
 }
 
-pub fn sys_exec(path: *const u8) -> isize {
+/// Return `argc` on success, which becomes `a0` of the new program.
+/// Return `EACCES` for a directory and `ENOEXEC` for a file that is not an elf.
+pub fn sys_exec(path: *const u8, args: *const usize, envs: *const usize) -> SyscallResult {
     let token = current_user_token();
-    let path = translated_str(token, path);
-    if let Some(app_inode) = open_file(path.as_str(), OpenFlags::RDONLY) {
-        let all_data = app_inode.read_all();
-        let task = current_task().unwrap();
-        task.exec(all_data.as_slice());
-        0
-    } else {
-        -1
+    let path = strncpy_from_user(token, path, PATH_MAX)?;
+    // the strings and their pointer arrays must leave room for the program
+    let mut space = USER_STACK_SIZE / 2;
+    let args = copy_str_array_from_user(token, args, &mut space)?;
+    let envs = copy_str_array_from_user(token, envs, &mut space)?;
+    let app_inode = open_file(path.as_str(), OpenFlags::RDONLY)?
+        .inode()
+        .unwrap();
+    // like Linux, a directory is not executable rather than a malformed elf
+    if app_inode.is_dir() {
+        return Err(Errno::EACCES);
     }
+    let task = current_task().unwrap();
+    let argc = args.len();
+    task.exec(&app_inode, args, envs)?;
+    Ok(argc as isize)
 }
 
-/// If there is not a child process whose pid is same as given, return -1.
-/// Else if there is a child process but it is still running, return -2.
-pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
+/// If there is not a child process whose pid is same as given, return `ECHILD`.
+/// Else if there is a child process but it is still running, return `EAGAIN`.
+pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> SyscallResult {
     let task = current_task().unwrap();
     // find a child process
 
     // ---- access current PCB exclusively
-    let mut inner = task.inner_exclusive_access();
+    let inner = task.inner_exclusive_access();
     if !inner
         .children
         .iter()
         .any(|p| pid == -1 || pid as usize == p.getpid())
     {
-        return -1;
+        return Err(Errno::ECHILD);
         // ---- release current PCB
     }
     let pair = inner.children.iter().enumerate().find(|(_, p)| {
         // ++++ temporarily access child PCB exclusively
-        p.inner_exclusive_access().is_zombie() && (pid == -1 || pid as usize == p.getpid())
+        p.inner_exclusive_access().is_zombie()
+            && (pid == -1 || pid as usize == p.getpid())
+            // the hart the child exited on may not have switched off its kernel stack yet
+            && Arc::strong_count(p) == 1
         // ++++ release child PCB
     });
     if let Some((idx, _)) = pair {
-        let child = inner.children.remove(idx);
-        // confirm that child will be deallocated after being removed from children list
-        assert_eq!(Arc::strong_count(&child), 1);
-        let found_pid = child.getpid();
         // ++++ temporarily access child PCB exclusively
-        let exit_code = child.inner_exclusive_access().exit_code;
+        let exit_code = inner.children[idx].inner_exclusive_access().exit_code;
         // ++++ release child PCB
-        *translated_refmut(inner.memory_set.token(), exit_code_ptr) = exit_code;
-        found_pid as isize
+        let token = inner.memory_set.token();
+        // writing to user memory may have to handle a copy-on-write page,
+        // and the child is kept if it fails
+        drop(inner);
+        if !exit_code_ptr.is_null() {
+            copy_to_user(token, exit_code_ptr, &exit_code)?;
+        }
+        let child = task.inner_exclusive_access().children.remove(idx);
+        // confirm that child will be deallocated after being removed from children list
+        assert_eq!(Arc::strong_count(&child), 1);
+        Ok(child.getpid() as isize)
     } else {
-        -2
+        Err(Errno::EAGAIN)
     }
     // ---- release current PCB automatically
 }
+
+pub fn sys_kill(pid: usize, signum: u32) -> SyscallResult {
+    let signal = SignalFlags::from_signum(signum as usize).ok_or(Errno::EINVAL)?;
+    let task = pid2task(pid).ok_or(Errno::ESRCH)?;
+    task.inner_exclusive_access().signals |= signal;
+    Ok(0)
+}
+
+/// Set the action of `signum` if `action` is not NULL,
+/// and store the previous one to `old_action` if it is not NULL.
+pub fn sys_sigaction(
+    signum: usize,
+    action: *const SignalAction,
+    old_action: *mut SignalAction,
+) -> SyscallResult {
+    let token = current_user_token();
+    match SignalFlags::from_signum(signum) {
+        Some(signal) if !signal.intersects(SignalFlags::uncatchable()) => {}
+        _ => return Err(Errno::EINVAL),
+    }
+    // reading user memory may have to handle a lazy page, so do it before borrowing
+    let action = if action.is_null() {
+        None
+    } else {
+        Some(copy_from_user(token, action)?)
+    };
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let prev_action = inner.signal_actions.table[signum];
+    if let Some(mut action) = action {
+        action.mask = SignalFlags::from_bits_truncate(action.mask.bits());
+        inner.signal_actions.table[signum] = action;
+    }
+    // writing to user memory may have to handle a copy-on-write page
+    drop(inner);
+    if !old_action.is_null() {
+        copy_to_user(token, old_action, &prev_action)?;
+    }
+    Ok(0)
+}
+
+/// Set the blocked signals to `mask` and return the previous mask.
+pub fn sys_sigprocmask(mask: u32) -> SyscallResult {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let old_mask = inner.signal_mask;
+    inner.signal_mask = SignalFlags::from_bits_truncate(mask) - SignalFlags::unblockable();
+    Ok(old_mask.bits() as isize)
+}
+
+/// Return from a signal handler to the interrupted user context.
+pub fn sys_sigreturn() -> SyscallResult {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    if let Some(trap_ctx) = inner.trap_ctx_backup.take() {
+        inner.signal_mask = inner.signal_mask_backup;
+        *inner.get_trap_cx() = trap_ctx;
+        // the return value is written to a0, so give back the interrupted one
+        Ok(trap_ctx.x[10] as isize)
+    } else {
+        Err(Errno::EINVAL)
+    }
+}
+
+/// Set the priority of the current task and return it. A task with a higher
+/// priority runs more often under the stride or CFS scheduler, the FIFO scheduler
+/// ignores it. The priority must be at least 2.
+pub fn sys_set_priority(prio: isize) -> SyscallResult {
+    if prio < 2 {
+        return Err(Errno::EINVAL);
+    }
+    let task = current_task().unwrap();
+    task.inner_exclusive_access().stride = BIG_STRIDE / prio as u64;
+    Ok(prio)
+}
+
+/// Move the program break by `size` bytes and return the old one.
+pub fn sys_sbrk(size: i32) -> SyscallResult {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let old_brk = inner.change_program_brk(size).ok_or(Errno::ENOMEM)?;
+    Ok(old_brk as isize)
+}
+
+/// Check that `[addr, addr + len)` is a page-aligned, non-empty range
+/// of user space, and return its pages.
+fn user_page_range(addr: usize, len: usize) -> Option<(VirtPageNum, VirtPageNum)> {
+    if addr % PAGE_SIZE != 0 || len == 0 || len > USER_SPACE_END || addr > USER_SPACE_END - len {
+        return None;
+    }
+    Some((
+        VirtAddr::from(addr).floor(),
+        VirtAddr::from(addr + len).ceil(),
+    ))
+}
+
+/// Map `len` bytes at `addr`, or anywhere if `addr` is 0 or taken and
+/// `MAP_FIXED` is not given. The pages come from the file `fd` at `offset`,
+/// or are zeros with `MAP_ANONYMOUS`. Return the start of the mapping.
+pub fn sys_mmap(
+    addr: usize,
+    len: usize,
+    prot: u32,
+    flags: u32,
+    fd: usize,
+    offset: usize,
+) -> SyscallResult {
+    let prot = match MmapProt::from_bits(prot) {
+        Some(prot) if !prot.is_empty() => prot,
+        _ => return Err(Errno::EINVAL),
+    };
+    let flags = MmapFlags::from_bits(flags).ok_or(Errno::EINVAL)?;
+    let shared = match (
+        flags.contains(MmapFlags::SHARED),
+        flags.contains(MmapFlags::PRIVATE),
+    ) {
+        (true, false) => true,
+        (false, true) => false,
+        _ => return Err(Errno::EINVAL),
+    };
+    if len == 0 || len > USER_SPACE_END || offset % PAGE_SIZE != 0 {
+        return Err(Errno::EINVAL);
+    }
+    let hint = user_page_range(addr, len);
+    let page_count = (len + PAGE_SIZE - 1) / PAGE_SIZE;
+    // a writable page must also be readable in Sv39
+    let mut permission = MapPermission::U | MapPermission::R;
+    if prot.contains(MmapProt::WRITE) {
+        permission |= MapPermission::W;
+    }
+    if prot.contains(MmapProt::EXEC) {
+        permission |= MapPermission::X;
+    }
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let backing = if flags.contains(MmapFlags::ANONYMOUS) {
+        None
+    } else {
+        let file = match inner.fd_table.get(fd) {
+            Some(Some(file)) => file.clone(),
+            _ => return Err(Errno::EBADF),
+        };
+        let inode = match file.inode() {
+            Some(inode) if !inode.is_dir() => inode,
+            _ => return Err(Errno::ENODEV),
+        };
+        if !file.readable() || (shared && prot.contains(MmapProt::WRITE) && !file.writable()) {
+            return Err(Errno::EACCES);
+        }
+        // pages past the end of file are zeros, and not written back
+        let size = inode.size() as usize;
+        // written back pages need write access, like a write through the file
+        let write_access = if shared && prot.contains(MmapProt::WRITE) {
+            Some(WriteAccess::get(&inode)?)
+        } else {
+            None
+        };
+        Some(MapBacking {
+            inode,
+            offset,
+            len: size.saturating_sub(offset).min(len),
+            write_access,
+        })
+    };
+    // `is_free` never accepts page 0, so `addr` 0 lets the kernel pick
+    let start = match hint {
+        Some((start, end)) if inner.memory_set.is_free(start, end) => start,
+        _ if flags.contains(MmapFlags::FIXED) => return Err(Errno::EEXIST),
+        _ => inner
+            .memory_set
+            .find_free_area(page_count)
+            .ok_or(Errno::ENOMEM)?,
+    };
+    let start_va: VirtAddr = start.into();
+    let end_va: VirtAddr = VirtPageNum(start.0 + page_count).into();
+    inner
+        .memory_set
+        .insert_lazy_area(start_va, end_va, permission, shared, backing);
+    Ok(usize::from(start_va) as isize)
+}
+
+/// Unmap the pages in `[addr, addr + len)`, writing shared pages back to their files.
+/// Pages that are not mapped are skipped.
+pub fn sys_munmap(addr: usize, len: usize) -> SyscallResult {
+    let (start, end) = user_page_range(addr, len).ok_or(Errno::EINVAL)?;
+    let task = current_task().unwrap();
+    task.inner_exclusive_access().memory_set.munmap(start, end);
+    Ok(0)
+}
+
+/// Write the shared pages in `[addr, addr + len)` back to their files.
+/// The write-back is always done before returning.
+pub fn sys_msync(addr: usize, len: usize, flags: u32) -> SyscallResult {
+    if flags & !(MS_ASYNC | MS_INVALIDATE | MS_SYNC) != 0
+        || flags & (MS_ASYNC | MS_SYNC) == MS_ASYNC | MS_SYNC
+    {
+        return Err(Errno::EINVAL);
+    }
+    let (start, end) = user_page_range(addr, len).ok_or(Errno::EINVAL)?;
+    let task = current_task().unwrap();
+    task.inner_exclusive_access().memory_set.msync(start, end);
+    Ok(0)
+}
@@ -0,0 +1,41 @@
+//! Signal actions registered by `sigaction`
+
+use super::signal::{SignalFlags, MAX_SIG};
+
+/// Handler address meaning the default action
+pub const SIG_DFL: usize = 0;
+/// Handler address meaning the signal is ignored
+pub const SIG_IGN: usize = 1;
+
+/// Action taken on delivery of a signal
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SignalAction {
+    /// user address of the handler, or `SIG_DFL`/`SIG_IGN`
+    pub handler: usize,
+    /// signals blocked while the handler runs
+    pub mask: SignalFlags,
+}
+
+impl Default for SignalAction {
+    fn default() -> Self {
+        Self {
+            handler: SIG_DFL,
+            mask: SignalFlags::empty(),
+        }
+    }
+}
+
+/// Actions of all signals of a process, indexed by signal number
+#[derive(Clone)]
+pub struct SignalActions {
+    pub table: [SignalAction; MAX_SIG + 1],
+}
+
+impl Default for SignalActions {
+    fn default() -> Self {
+        Self {
+            table: [SignalAction::default(); MAX_SIG + 1],
+        }
+    }
+}
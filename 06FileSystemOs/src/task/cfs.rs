@@ -0,0 +1,44 @@
+//! Implementation of [`CfsScheduler`]
+use super::manager::Scheduler;
+use super::TaskControlBlock;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+
+/// A scheduler like the Completely Fair Scheduler of Linux, which runs the
+/// ready task that has run the least, by the virtual runtime charged in
+/// [`run_tasks`](super::run_tasks).
+///
+/// A task is charged before it is put back to the queue, but a fork child shares
+/// the virtual runtime of its parent, so the queue is simply searched on every fetch.
+pub struct CfsScheduler {
+    ready_queue: VecDeque<Arc<TaskControlBlock>>,
+    /// the virtual runtime of the last task fetched, which never goes back
+    min_vruntime: u64,
+}
+
+impl Scheduler for CfsScheduler {
+    fn new() -> Self {
+        Self {
+            ready_queue: VecDeque::new(),
+            min_vruntime: 0,
+        }
+    }
+    fn add(&mut self, task: Arc<TaskControlBlock>) {
+        // a task that has been away does not get the CPU to itself to catch up
+        let mut inner = task.inner_exclusive_access();
+        inner.vruntime = inner.vruntime.max(self.min_vruntime);
+        drop(inner);
+        self.ready_queue.push_back(task);
+    }
+    fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
+        // the first of the least, so that tasks with the same vruntime run in turn
+        let (idx, vruntime) = self
+            .ready_queue
+            .iter()
+            .map(|task| task.inner_exclusive_access().vruntime)
+            .enumerate()
+            .min_by_key(|&(_, vruntime)| vruntime)?;
+        self.min_vruntime = self.min_vruntime.max(vruntime);
+        self.ready_queue.remove(idx)
+    }
+}
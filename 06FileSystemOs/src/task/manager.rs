@@ -1,40 +1,64 @@
 //!Implementation of [`TaskManager`]
 use super::TaskControlBlock;
-use crate::sync::UPSafeCell;
-use alloc::collections::VecDeque;
+use crate::sync::SpinLock;
+use alloc::collections::BTreeMap;
 use alloc::sync::Arc;
 use lazy_static::*;
-///A array of `TaskControlBlock` that is thread-safe
-pub struct TaskManager {
-    ready_queue: VecDeque<Arc<TaskControlBlock>>,
-}
+/// The stride of a task with priority 1. As priorities are at least 2,
+/// passes of ready tasks differ by at most half of it, and can be
+/// compared correctly even after they wrap around.
+pub const BIG_STRIDE: u64 = 1 << 20;
+/// Priority of a new task
+pub const DEFAULT_PRIORITY: u64 = 16;
 
-/// A simple FIFO scheduler.
-impl TaskManager {
-    ///Creat an empty TaskManager
-    pub fn new() -> Self {
-        Self {
-            ready_queue: VecDeque::new(),
-        }
-    }
-    ///Add a task to `TaskManager`
-    pub fn add(&mut self, task: Arc<TaskControlBlock>) {
-        self.ready_queue.push_back(task);
-    }
-    ///Remove the first task and return it,or `None` if `TaskManager` is empty
-    pub fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
-        self.ready_queue.pop_front()
-    }
+/// A scheduling policy, which keeps the ready tasks and picks the next one to run
+pub trait Scheduler {
+    /// Create an empty ready queue
+    fn new() -> Self;
+    /// Add a ready task
+    fn add(&mut self, task: Arc<TaskControlBlock>);
+    /// Remove the next task to run and return it, or `None` if no task is ready
+    fn fetch(&mut self) -> Option<Arc<TaskControlBlock>>;
 }
 
+/// The scheduling policy, selected by the `stride` or `cfs` feature at build time
+#[cfg(not(any(feature = "stride", feature = "cfs")))]
+pub type TaskManager = super::fifo::FifoScheduler;
+/// The scheduling policy, selected by the `stride` or `cfs` feature at build time
+#[cfg(feature = "stride")]
+pub type TaskManager = super::stride::StrideScheduler;
+/// The scheduling policy, selected by the `stride` or `cfs` feature at build time
+#[cfg(feature = "cfs")]
+pub type TaskManager = super::cfs::CfsScheduler;
+
 lazy_static! {
-    pub static ref TASK_MANAGER: UPSafeCell<TaskManager> =
-        unsafe { UPSafeCell::new(TaskManager::new()) };
+    pub static ref TASK_MANAGER: SpinLock<TaskManager> =
+        SpinLock::new(TaskManager::new());
+    /// Live tasks by pid, so that signals can be sent to them
+    pub static ref PID2TCB: SpinLock<BTreeMap<usize, Arc<TaskControlBlock>>> =
+        SpinLock::new(BTreeMap::new());
 }
 ///Interface offered to add task
 pub fn add_task(task: Arc<TaskControlBlock>) {
+    PID2TCB
+        .exclusive_access()
+        .insert(task.getpid(), Arc::clone(&task));
     TASK_MANAGER.exclusive_access().add(task);
 }
+///Put a task that was running back to the ready queue, it is still in `PID2TCB`
+pub fn requeue_task(task: Arc<TaskControlBlock>) {
+    TASK_MANAGER.exclusive_access().add(task);
+}
+///Find a live task by pid
+pub fn pid2task(pid: usize) -> Option<Arc<TaskControlBlock>> {
+    PID2TCB.exclusive_access().get(&pid).map(Arc::clone)
+}
+///Forget an exited task
+pub fn remove_from_pid2task(pid: usize) {
+    if PID2TCB.exclusive_access().remove(&pid).is_none() {
+        panic!("cannot find pid {} in pid2task!", pid);
+    }
+}
 ///Interface offered to pop the first task
 pub fn fetch_task() -> Option<Arc<TaskControlBlock>> {
     TASK_MANAGER.exclusive_access().fetch()
@@ -4,40 +4,60 @@
 //! implemented here.
 //!
 //! A single global instance of [`TaskManager`] called `TASK_MANAGER` controls
-//! all the tasks in the whole operating system.
+//! all the tasks in the whole operating system. Its scheduling policy is FIFO,
+//! stride scheduling with the `stride` feature, or fair scheduling by virtual
+//! runtime with the `cfs` feature.
 //!
-//! A single global instance of [`Processor`] called `PROCESSOR` monitors running
-//! task(s) for each core.
+//! One instance of [`Processor`] for each hart, in `PROCESSORS`, monitors the
+//! task running on that hart. All harts fetch tasks from the shared `TASK_MANAGER`.
 //!
 //! A single global instance of [`PidAllocator`] called `PID_ALLOCATOR` allocates
 //! pid for user apps.
 //!
 //! Be careful when you see `__switch` ASM function in `switch.S`. Control flow around this function
 //! might not be what you expect.
+mod action;
+#[cfg(feature = "cfs")]
+mod cfs;
 mod context;
+#[cfg(not(any(feature = "stride", feature = "cfs")))]
+mod fifo;
 mod manager;
 mod pid;
 mod processor;
+mod signal;
+mod sleep;
+#[cfg(feature = "stride")]
+mod stride;
 mod switch;
 #[allow(clippy::module_inception)]
 #[allow(rustdoc::private_intra_doc_links)]
 mod task;
 
-use crate::fs::{open_file, OpenFlags};
+#[cfg(all(feature = "stride", feature = "cfs"))]
+compile_error!("features `stride` and `cfs` select different schedulers");
+
+use crate::fs::{open_file, File, OpenFlags};
+use crate::mm::VirtAddr;
 use crate::sbi::shutdown;
 use alloc::sync::Arc;
 pub use context::TaskContext;
 use lazy_static::*;
 pub use manager::{fetch_task, TaskManager};
 use switch::__switch;
-use task::{TaskControlBlock, TaskStatus};
+use task::{TaskControlBlock, TaskControlBlockInner, TaskStatus};
 
-pub use manager::add_task;
+pub use action::{SignalAction, SIG_DFL, SIG_IGN};
+pub use manager::{
+    add_task, pid2task, remove_from_pid2task, requeue_task, BIG_STRIDE, DEFAULT_PRIORITY,
+};
 pub use pid::{pid_alloc, KernelStack, PidAllocator, PidHandle};
 pub use processor::{
-    current_task, current_trap_cx, current_user_token, run_tasks, schedule, take_current_task,
-    Processor,
+    current_task, current_trap_cx, current_user_token, hart_id, run_tasks, schedule,
+    take_current_task, Processor,
 };
+pub use signal::{DefaultAction, SignalFlags, MAX_SIG};
+pub use sleep::{add_timer, check_timer};
 /// Suspend the current 'Running' task and run the next task in task list.
 pub fn suspend_current_and_run_next() {
     // There must be an application running.
@@ -51,12 +71,36 @@ pub fn suspend_current_and_run_next() {
     drop(task_inner);
     // ---- release current PCB
 
-    // push back to ready queue.
-    add_task(task);
-    // jump to scheduling cycle
+    // jump to scheduling cycle, which pushes the task back to ready queue
+    // once its context is saved, so that no other hart can run it before
+    schedule(task_cx_ptr);
+}
+
+/// Block the current 'Running' task until [`wakeup_task`], and run the next task in task list.
+pub fn block_current_and_run_next() {
+    let task = take_current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    let task_cx_ptr = &mut task_inner.task_cx as *mut TaskContext;
+    // the task stays 'Running' until `run_tasks` has saved its context
+    // and marks it 'Blocked', so that a wakeup in between is not lost
+    drop(task_inner);
     schedule(task_cx_ptr);
 }
 
+/// Make a task blocked by [`block_current_and_run_next`] ready to run again
+pub fn wakeup_task(task: Arc<TaskControlBlock>) {
+    let mut task_inner = task.inner_exclusive_access();
+    let blocked = task_inner.task_status == TaskStatus::Blocked;
+    // a task still switching out is pushed back to ready queue by `run_tasks`
+    if blocked || task_inner.task_status == TaskStatus::Running {
+        task_inner.task_status = TaskStatus::Ready;
+    }
+    drop(task_inner);
+    if blocked {
+        requeue_task(task);
+    }
+}
+
 /// pid of usertests app in make run TEST=1
 pub const IDLE_PID: usize = 0;
 
@@ -80,27 +124,34 @@ pub fn exit_current_and_run_next(exit_code: i32) {
         }
     }
 
+    remove_from_pid2task(pid);
     // **** access current TCB exclusively
     let mut inner = task.inner_exclusive_access();
-    // Change status to Zombie
-    inner.task_status = TaskStatus::Zombie;
-    // Record exit code
-    inner.exit_code = exit_code;
-    // do not move to its parent but under initproc
+    let children = core::mem::take(&mut inner.children);
+    drop(inner);
+    // **** release current PCB, as a parent must be locked before its children
 
+    // do not move to its parent but under initproc
     // ++++++ access initproc TCB exclusively
     {
         let mut initproc_inner = INITPROC.inner_exclusive_access();
-        for child in inner.children.iter() {
+        for child in children {
             child.inner_exclusive_access().parent = Some(Arc::downgrade(&INITPROC));
-            initproc_inner.children.push(child.clone());
+            initproc_inner.children.push(child);
         }
     }
     // ++++++ release parent PCB
 
-    inner.children.clear();
+    // **** access current TCB exclusively
+    let mut inner = task.inner_exclusive_access();
+    // Record exit code
+    inner.exit_code = exit_code;
+    // close all files, so that pipe ends held by a zombie are released
+    inner.fd_table.clear();
     // deallocate user space
     inner.memory_set.recycle_data_pages();
+    // Change status to Zombie, the parent may reap the task from now on
+    inner.task_status = TaskStatus::Zombie;
     drop(inner);
     // **** release current PCB
     // drop task manually to maintain rc correctly
@@ -113,12 +164,127 @@ pub fn exit_current_and_run_next(exit_code: i32) {
 lazy_static! {
     ///Globle process that init user shell
     pub static ref INITPROC: Arc<TaskControlBlock> = Arc::new({
-        let inode = open_file("initproc", OpenFlags::RDONLY).unwrap();
-        let v = inode.read_all();
-        TaskControlBlock::new(v.as_slice())
+        let app_inode = open_file("initproc", OpenFlags::RDONLY).unwrap();
+        TaskControlBlock::new(&app_inode.inode().unwrap())
     });
 }
 ///Add init process to the manager
 pub fn add_initproc() {
     add_task(INITPROC.clone());
 }
+
+/// Handle a page fault of the current task at `va`, see [`crate::mm::MemorySet::handle_page_fault`].
+/// Return false if the access is not allowed.
+pub fn current_page_fault(va: usize, write: bool) -> bool {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    inner
+        .memory_set
+        .handle_page_fault(VirtAddr::from(va).floor(), write)
+}
+
+/// Raise `signal` on the current task
+pub fn current_add_signal(signal: SignalFlags) {
+    let task = current_task().unwrap();
+    task.inner_exclusive_access().signals |= signal;
+}
+
+/// Act on the first deliverable pending signal, which may start a user handler.
+/// Return the exit code and a message if the task must be terminated.
+fn check_pending_signals(inner: &mut TaskControlBlockInner) -> Option<(i32, &'static str)> {
+    for signum in 1..=MAX_SIG {
+        let signal = SignalFlags::from_signum(signum).unwrap();
+        if !inner.signals.contains(signal) || inner.signal_mask.contains(signal) {
+            continue;
+        }
+        match signal {
+            SignalFlags::SIGKILL => return signal.check_error(),
+            SignalFlags::SIGSTOP => {
+                inner.signals.remove(signal);
+                inner.frozen = true;
+            }
+            SignalFlags::SIGCONT => {
+                inner.signals.remove(signal);
+                inner.frozen = false;
+            }
+            _ => match inner.signal_actions.table[signum].handler {
+                SIG_IGN if !signal.intersects(SignalFlags::faults()) => {
+                    inner.signals.remove(signal)
+                }
+                // an ignored fault would only trap again
+                SIG_DFL | SIG_IGN => {
+                    inner.signals.remove(signal);
+                    match signal.default_action() {
+                        DefaultAction::Terminate => return signal.check_error(),
+                        DefaultAction::Ignore => {}
+                        DefaultAction::Stop => inner.frozen = true,
+                        DefaultAction::Continue => inner.frozen = false,
+                    }
+                }
+                handler => {
+                    // only one handler runs at a time, the others wait for sigreturn
+                    if inner.trap_ctx_backup.is_some() {
+                        // the faulting instruction would only trap again
+                        if signal.intersects(SignalFlags::faults()) {
+                            return signal.check_error();
+                        }
+                        continue;
+                    }
+                    inner.signals.remove(signal);
+                    let trap_ctx = inner.get_trap_cx();
+                    inner.trap_ctx_backup = Some(*trap_ctx);
+                    trap_ctx.sepc = handler;
+                    trap_ctx.x[10] = signum;
+                    inner.signal_mask_backup = inner.signal_mask;
+                    inner.signal_mask |= inner.signal_actions.table[signum].mask | signal;
+                    inner.signal_mask.remove(SignalFlags::unblockable());
+                    return None;
+                }
+            },
+        }
+    }
+    None
+}
+
+/// Whether a pending signal of the current task will be acted on when it returns
+/// to user mode, so that a syscall waiting for something else should return early
+pub fn current_signal_pending() -> bool {
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    (1..=MAX_SIG).any(|signum| {
+        let signal = SignalFlags::from_signum(signum).unwrap();
+        if !inner.signals.contains(signal) || inner.signal_mask.contains(signal) {
+            return false;
+        }
+        match signal {
+            SignalFlags::SIGKILL => true,
+            SignalFlags::SIGSTOP | SignalFlags::SIGCONT => false,
+            _ => match inner.signal_actions.table[signum].handler {
+                SIG_IGN => false,
+                SIG_DFL => signal.default_action() == DefaultAction::Terminate,
+                // a handler waits for the running one to return
+                _ => inner.trap_ctx_backup.is_none(),
+            },
+        }
+    })
+}
+
+/// Handle pending signals of the current task before it returns to user mode
+pub fn handle_signals() {
+    loop {
+        let task = current_task().unwrap();
+        let mut inner = task.inner_exclusive_access();
+        let error = check_pending_signals(&mut inner);
+        let frozen = inner.frozen;
+        drop(inner);
+        drop(task);
+        if let Some((exit_code, msg)) = error {
+            println!("[kernel] {}", msg);
+            exit_current_and_run_next(exit_code);
+        }
+        if !frozen {
+            break;
+        }
+        suspend_current_and_run_next();
+    }
+}
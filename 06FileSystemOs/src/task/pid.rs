@@ -1,7 +1,7 @@
 //!Implementation of [`PidAllocator`]
 use crate::config::{KERNEL_STACK_SIZE, PAGE_SIZE, TRAMPOLINE};
 use crate::mm::{MapPermission, VirtAddr, KERNEL_SPACE};
-use crate::sync::UPSafeCell;
+use crate::sync::SpinLock;
 use alloc::vec::Vec;
 use lazy_static::*;
 ///Pid Allocator struct
@@ -40,8 +40,7 @@ impl PidAllocator {
 }
 
 lazy_static! {
-    pub static ref PID_ALLOCATOR: UPSafeCell<PidAllocator> =
-        unsafe { UPSafeCell::new(PidAllocator::new()) };
+    pub static ref PID_ALLOCATOR: SpinLock<PidAllocator> = SpinLock::new(PidAllocator::new());
 }
 ///Bind pid lifetime to `PidHandle`
 pub struct PidHandle(pub usize);
@@ -1,11 +1,17 @@
 //!Implementation of [`Processor`] and Intersection of control flow
 use super::__switch;
-use super::{fetch_task, TaskStatus};
+use super::{check_timer, fetch_task, requeue_task, TaskStatus, BIG_STRIDE, DEFAULT_PRIORITY};
 use super::{TaskContext, TaskControlBlock};
-use crate::sync::UPSafeCell;
+use crate::config::MAX_HARTS;
+use crate::mm::set_running_token;
+use crate::sync::{SpinLock, SpinLockGuard};
+use crate::timer::{get_time, set_next_trigger};
 use crate::trap::TrapContext;
 use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::arch::asm;
 use lazy_static::*;
+use riscv::register::sip;
 ///Processor management structure
 pub struct Processor {
     ///The task currently executing on the current processor
@@ -37,37 +43,85 @@ impl Processor {
 }
 
 lazy_static! {
-    pub static ref PROCESSOR: UPSafeCell<Processor> = unsafe { UPSafeCell::new(Processor::new()) };
+    /// One processor for each hart, indexed by hartid
+    pub static ref PROCESSORS: Vec<SpinLock<Processor>> =
+        (0..MAX_HARTS).map(|_| SpinLock::new(Processor::new())).collect();
+}
+///The id of the current hart, which the kernel keeps in `tp`
+pub fn hart_id() -> usize {
+    let hartid;
+    unsafe {
+        asm!("mv {}, tp", out(reg) hartid);
+    }
+    hartid
+}
+///The processor of the current hart
+fn current_processor() -> SpinLockGuard<'static, Processor> {
+    PROCESSORS[hart_id()].exclusive_access()
 }
 ///The main part of process execution and scheduling
 ///Loop `fetch_task` to get the process that needs to run, and switch the process through `__switch`
 pub fn run_tasks() {
     loop {
-        let mut processor = PROCESSOR.exclusive_access();
+        let mut processor = current_processor();
         if let Some(task) = fetch_task() {
             let idle_task_cx_ptr = processor.get_idle_task_cx_ptr();
             // access coming task TCB exclusively
             let mut task_inner = task.inner_exclusive_access();
             let next_task_cx_ptr = &task_inner.task_cx as *const TaskContext;
             task_inner.task_status = TaskStatus::Running;
+            // before the task can run, so that no other hart evicts a page
+            // it is about to use
+            set_running_token(task_inner.get_user_token());
             drop(task_inner);
             // release coming task TCB manually
-            processor.current = Some(task);
+            processor.current = Some(task.clone());
             // release processor manually
             drop(processor);
+            let switch_in = get_time();
             unsafe {
+                // the task may have run on another hart, whose kernel stack mappings
+                // we may not have seen yet
+                asm!("sfence.vma");
                 __switch(idle_task_cx_ptr, next_task_cx_ptr);
             }
+            // back from the task, no user page table is loaded on this hart any more
+            set_running_token(0);
+            // charge the time it ran, weighted by its priority
+            let mut task_inner = task.inner_exclusive_access();
+            let delta = (get_time() - switch_in) as u64;
+            task_inner.vruntime += delta * task_inner.stride / (BIG_STRIDE / DEFAULT_PRIORITY);
+            let status = task_inner.task_status;
+            // a task switching out while still 'Running' has blocked itself
+            if status == TaskStatus::Running {
+                task_inner.task_status = TaskStatus::Blocked;
+            }
+            drop(task_inner);
+            // its context is saved, other harts may run it now
+            if status == TaskStatus::Ready {
+                requeue_task(task);
+            }
+        } else {
+            drop(processor);
+            // nothing is runnable, wait for the next timer interrupt. Interrupts are
+            // off in the kernel, so it stays pending instead of trapping.
+            unsafe {
+                asm!("wfi");
+            }
+            if sip::read().stimer() {
+                set_next_trigger();
+            }
+            check_timer();
         }
     }
 }
 ///Take the current task,leaving a None in its place
 pub fn take_current_task() -> Option<Arc<TaskControlBlock>> {
-    PROCESSOR.exclusive_access().take_current()
+    current_processor().take_current()
 }
 ///Get running task
 pub fn current_task() -> Option<Arc<TaskControlBlock>> {
-    PROCESSOR.exclusive_access().current()
+    current_processor().current()
 }
 ///Get token of the address space of current task
 pub fn current_user_token() -> usize {
@@ -84,7 +138,7 @@ pub fn current_trap_cx() -> &'static mut TrapContext {
 }
 ///Return to idle control flow for new scheduling
 pub fn schedule(switched_task_cx_ptr: *mut TaskContext) {
-    let mut processor = PROCESSOR.exclusive_access();
+    let mut processor = current_processor();
     let idle_task_cx_ptr = processor.get_idle_task_cx_ptr();
     drop(processor);
     unsafe {
@@ -0,0 +1,144 @@
+//! Signal numbers and the default actions of signals
+
+use bitflags::*;
+
+/// The largest signal number
+pub const MAX_SIG: usize = 31;
+
+bitflags! {
+    /// A set of signals, where signal `n` is bit `n`
+    pub struct SignalFlags: u32 {
+        const SIGHUP = 1 << 1;
+        const SIGINT = 1 << 2;
+        const SIGQUIT = 1 << 3;
+        const SIGILL = 1 << 4;
+        const SIGTRAP = 1 << 5;
+        const SIGABRT = 1 << 6;
+        const SIGBUS = 1 << 7;
+        const SIGFPE = 1 << 8;
+        const SIGKILL = 1 << 9;
+        const SIGUSR1 = 1 << 10;
+        const SIGSEGV = 1 << 11;
+        const SIGUSR2 = 1 << 12;
+        const SIGPIPE = 1 << 13;
+        const SIGALRM = 1 << 14;
+        const SIGTERM = 1 << 15;
+        const SIGSTKFLT = 1 << 16;
+        const SIGCHLD = 1 << 17;
+        const SIGCONT = 1 << 18;
+        const SIGSTOP = 1 << 19;
+        const SIGTSTP = 1 << 20;
+        const SIGTTIN = 1 << 21;
+        const SIGTTOU = 1 << 22;
+        const SIGURG = 1 << 23;
+        const SIGXCPU = 1 << 24;
+        const SIGXFSZ = 1 << 25;
+        const SIGVTALRM = 1 << 26;
+        const SIGPROF = 1 << 27;
+        const SIGWINCH = 1 << 28;
+        const SIGIO = 1 << 29;
+        const SIGPWR = 1 << 30;
+        const SIGSYS = 1 << 31;
+    }
+}
+
+impl SignalFlags {
+    /// The set holding only signal `signum`, or `None` for an invalid number
+    pub fn from_signum(signum: usize) -> Option<Self> {
+        if signum == 0 || signum > MAX_SIG {
+            return None;
+        }
+        Self::from_bits(1 << signum)
+    }
+
+    /// Signals that always take their default action
+    pub fn uncatchable() -> Self {
+        Self::SIGKILL | Self::SIGSTOP
+    }
+
+    /// Signals raised by faults, since the faulting instruction would trap again
+    /// if they were left pending
+    pub fn faults() -> Self {
+        Self::SIGILL | Self::SIGSEGV
+    }
+
+    /// Signals that can not be blocked
+    pub fn unblockable() -> Self {
+        Self::uncatchable() | Self::faults()
+    }
+
+    /// The default action of the first signal in the set
+    pub fn default_action(&self) -> DefaultAction {
+        if self.intersects(Self::SIGCHLD | Self::SIGURG | Self::SIGWINCH) {
+            DefaultAction::Ignore
+        } else if self.intersects(Self::SIGSTOP | Self::SIGTSTP | Self::SIGTTIN | Self::SIGTTOU) {
+            DefaultAction::Stop
+        } else if self.contains(Self::SIGCONT) {
+            DefaultAction::Continue
+        } else {
+            DefaultAction::Terminate
+        }
+    }
+
+    /// If the default action of one of these signals terminates the process,
+    /// return the exit code and a message for the first of them.
+    pub fn check_error(&self) -> Option<(i32, &'static str)> {
+        (1..=MAX_SIG).find_map(|signum| {
+            let signal = Self::from_signum(signum).unwrap();
+            if self.contains(signal) && signal.default_action() == DefaultAction::Terminate {
+                Some((-(signum as i32), TERMINATE_MESSAGES[signum]))
+            } else {
+                None
+            }
+        })
+    }
+}
+
+/// What happens to a process on a signal it neither handles nor ignores
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum DefaultAction {
+    /// exit with the negated signal number
+    Terminate,
+    /// discard the signal
+    Ignore,
+    /// freeze until `SIGCONT`
+    Stop,
+    /// resume a stopped process
+    Continue,
+}
+
+/// Messages printed when a process is terminated by a signal, by signal number
+const TERMINATE_MESSAGES: [&str; MAX_SIG + 1] = [
+    "",
+    "Hangup, SIGHUP=1",
+    "Interrupt, SIGINT=2",
+    "Quit, SIGQUIT=3",
+    "Illegal Instruction, SIGILL=4",
+    "Trace/breakpoint trap, SIGTRAP=5",
+    "Aborted, SIGABRT=6",
+    "Bus error, SIGBUS=7",
+    "Erroneous Arithmetic Operation, SIGFPE=8",
+    "Killed, SIGKILL=9",
+    "User defined signal 1, SIGUSR1=10",
+    "Segmentation Fault, SIGSEGV=11",
+    "User defined signal 2, SIGUSR2=12",
+    "Broken pipe, SIGPIPE=13",
+    "Alarm clock, SIGALRM=14",
+    "Terminated, SIGTERM=15",
+    "Stack fault, SIGSTKFLT=16",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "",
+    "CPU time limit exceeded, SIGXCPU=24",
+    "File size limit exceeded, SIGXFSZ=25",
+    "Virtual timer expired, SIGVTALRM=26",
+    "Profiling timer expired, SIGPROF=27",
+    "",
+    "I/O possible, SIGIO=29",
+    "Power failure, SIGPWR=30",
+    "Bad system call, SIGSYS=31",
+];
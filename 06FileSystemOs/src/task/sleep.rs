@@ -0,0 +1,67 @@
+//! Sleeping tasks, woken by the timer interrupt
+use super::{wakeup_task, TaskControlBlock};
+use crate::sync::SpinLock;
+use crate::timer::get_time;
+use alloc::collections::BinaryHeap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use lazy_static::*;
+
+/// A sleeping task with the time it wakes up at
+struct SleepEntry {
+    /// in ticks of the `time` CSR
+    expire: usize,
+    task: Arc<TaskControlBlock>,
+}
+
+impl PartialEq for SleepEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.expire == other.expire
+    }
+}
+
+impl Eq for SleepEntry {}
+
+impl PartialOrd for SleepEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SleepEntry {
+    /// Reversed, so that `BinaryHeap` pops the earliest deadline first
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.expire.cmp(&self.expire)
+    }
+}
+
+lazy_static! {
+    /// sleeping tasks, by deadline
+    static ref SLEEPERS: SpinLock<BinaryHeap<SleepEntry>> = SpinLock::new(BinaryHeap::new());
+}
+
+/// Wake `task` up with [`wakeup_task`] once `get_time()` reaches `expire`
+pub fn add_timer(expire: usize, task: Arc<TaskControlBlock>) {
+    SLEEPERS
+        .exclusive_access()
+        .push(SleepEntry { expire, task });
+}
+
+/// Wake up the tasks whose deadlines have passed
+pub fn check_timer() {
+    let now = get_time();
+    let mut expired = Vec::new();
+    let mut sleepers = SLEEPERS.exclusive_access();
+    while let Some(entry) = sleepers.peek() {
+        if entry.expire > now {
+            break;
+        }
+        expired.push(sleepers.pop().unwrap().task);
+    }
+    // waking takes the task and ready queue locks
+    drop(sleepers);
+    for task in expired {
+        wakeup_task(task);
+    }
+}
@@ -0,0 +1,71 @@
+//! Implementation of [`StrideScheduler`]
+use super::manager::Scheduler;
+use super::TaskControlBlock;
+use alloc::collections::BinaryHeap;
+use alloc::sync::Arc;
+use core::cmp::Ordering;
+
+/// A ready task with its pass when it was added
+struct StrideEntry {
+    pass: u64,
+    /// order of adding, so that tasks with the same pass run in turn
+    seq: usize,
+    task: Arc<TaskControlBlock>,
+}
+
+impl PartialEq for StrideEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for StrideEntry {}
+
+impl PartialOrd for StrideEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for StrideEntry {
+    /// Reversed, so that `BinaryHeap` pops the smallest pass first.
+    /// Passes are compared by their difference, see [`BIG_STRIDE`](super::BIG_STRIDE).
+    fn cmp(&self, other: &Self) -> Ordering {
+        (other.pass.wrapping_sub(self.pass) as i64)
+            .cmp(&0)
+            .then(other.seq.cmp(&self.seq))
+    }
+}
+
+/// A stride scheduler, which runs the ready task with the smallest pass,
+/// and then advances its pass by its stride.
+/// A task gets a share of time in proportion to its priority.
+pub struct StrideScheduler {
+    ready_queue: BinaryHeap<StrideEntry>,
+    next_seq: usize,
+}
+
+impl Scheduler for StrideScheduler {
+    fn new() -> Self {
+        Self {
+            ready_queue: BinaryHeap::new(),
+            next_seq: 0,
+        }
+    }
+    fn add(&mut self, task: Arc<TaskControlBlock>) {
+        let pass = task.inner_exclusive_access().pass;
+        self.ready_queue.push(StrideEntry {
+            pass,
+            seq: self.next_seq,
+            task,
+        });
+        self.next_seq += 1;
+    }
+    fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
+        let task = self.ready_queue.pop()?.task;
+        let mut inner = task.inner_exclusive_access();
+        inner.pass = inner.pass.wrapping_add(inner.stride);
+        drop(inner);
+        Some(task)
+    }
+}
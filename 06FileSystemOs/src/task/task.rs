@@ -1,28 +1,40 @@
 //!Implementation of [`TaskControlBlock`]
+use super::action::SignalActions;
+use super::signal::SignalFlags;
 use super::TaskContext;
-use super::{pid_alloc, KernelStack, PidHandle};
+use super::{pid_alloc, KernelStack, PidHandle, BIG_STRIDE, DEFAULT_PRIORITY};
 use crate::config::TRAP_CONTEXT;
 use crate::fs::{File, Stdin, Stdout};
-use crate::mm::{MemorySet, PhysPageNum, VirtAddr, KERNEL_SPACE};
-use crate::sync::UPSafeCell;
+use crate::mm::{
+    set_running_token, translated_byte_buffer, MemorySet, PhysPageNum, UserBuffer, VirtAddr,
+    KERNEL_SPACE,
+};
+use crate::sync::{SpinLock, SpinLockGuard};
+use crate::syscall::Errno;
 use crate::trap::{trap_handler, TrapContext};
+use alloc::string::String;
 use alloc::sync::{Arc, Weak};
 use alloc::vec;
 use alloc::vec::Vec;
-use core::cell::RefMut;
+use core::mem::size_of;
+use easy_fs::Inode;
 
 pub struct TaskControlBlock {
     // immutable
     pub pid: PidHandle,
     pub kernel_stack: KernelStack,
     // mutable
-    inner: UPSafeCell<TaskControlBlockInner>,
+    inner: SpinLock<TaskControlBlockInner>,
 }
 
 pub struct TaskControlBlockInner {
     pub trap_cx_ppn: PhysPageNum,
     #[allow(unused)]
     pub base_size: usize,
+    /// start of the heap, right above the user stack
+    pub heap_bottom: usize,
+    /// end of the heap, moved by sbrk
+    pub program_brk: usize,
     pub task_cx: TaskContext,
     pub task_status: TaskStatus,
     pub memory_set: MemorySet,
@@ -30,6 +42,23 @@ pub struct TaskControlBlockInner {
     pub children: Vec<Arc<TaskControlBlock>>,
     pub exit_code: i32,
     pub fd_table: Vec<Option<Arc<dyn File + Send + Sync>>>,
+    /// pending signals
+    pub signals: SignalFlags,
+    /// blocked signals
+    pub signal_mask: SignalFlags,
+    pub signal_actions: SignalActions,
+    /// stopped by SIGSTOP until SIGCONT arrives
+    pub frozen: bool,
+    /// user context interrupted by the running signal handler
+    pub trap_ctx_backup: Option<TrapContext>,
+    /// `signal_mask` before the running signal handler
+    pub signal_mask_backup: SignalFlags,
+    /// `BIG_STRIDE` divided by the priority
+    pub stride: u64,
+    /// how far the task has run in the stride scheduler
+    pub pass: u64,
+    /// time the task has run, weighted by its priority, in clock cycles
+    pub vruntime: u64,
 }
 
 impl TaskControlBlockInner {
@@ -53,15 +82,36 @@ impl TaskControlBlockInner {
             self.fd_table.len() - 1
         }
     }
+    /// Move the program break by `size` bytes and return the old one
+    pub fn change_program_brk(&mut self, size: i32) -> Option<usize> {
+        let old_brk = self.program_brk;
+        let new_brk = self.program_brk as isize + size as isize;
+        if new_brk < self.heap_bottom as isize {
+            return None;
+        }
+        let result = if size < 0 {
+            self.memory_set
+                .shrink_to(VirtAddr(self.heap_bottom), VirtAddr(new_brk as usize))
+        } else {
+            self.memory_set
+                .append_to(VirtAddr(self.heap_bottom), VirtAddr(new_brk as usize))
+        };
+        if result {
+            self.program_brk = new_brk as usize;
+            Some(old_brk)
+        } else {
+            None
+        }
+    }
 }
 
 impl TaskControlBlock {
-    pub fn inner_exclusive_access(&self) -> RefMut<'_, TaskControlBlockInner> {
+    pub fn inner_exclusive_access(&self) -> SpinLockGuard<'_, TaskControlBlockInner> {
         self.inner.exclusive_access()
     }
-    pub fn new(elf_data: &[u8]) -> Self {
+    pub fn new(elf_inode: &Arc<Inode>) -> Self {
         // memory_set with elf program headers/trampoline/trap context/user stack
-        let (memory_set, user_sp, entry_point) = MemorySet::from_elf(elf_data);
+        let (memory_set, user_sp, entry_point) = MemorySet::from_elf(elf_inode).unwrap();
         let trap_cx_ppn = memory_set
             .translate(VirtAddr::from(TRAP_CONTEXT).into())
             .unwrap()
@@ -73,26 +123,35 @@ impl TaskControlBlock {
         let task_control_block = Self {
             pid: pid_handle,
             kernel_stack,
-            inner: unsafe {
-                UPSafeCell::new(TaskControlBlockInner {
-                    trap_cx_ppn,
-                    base_size: user_sp,
-                    task_cx: TaskContext::goto_trap_return(kernel_stack_top),
-                    task_status: TaskStatus::Ready,
-                    memory_set,
-                    parent: None,
-                    children: Vec::new(),
-                    exit_code: 0,
-                    fd_table: vec![
-                        // 0 -> stdin
-                        Some(Arc::new(Stdin)),
-                        // 1 -> stdout
-                        Some(Arc::new(Stdout)),
-                        // 2 -> stderr
-                        Some(Arc::new(Stdout)),
-                    ],
-                })
-            },
+            inner: SpinLock::new(TaskControlBlockInner {
+                trap_cx_ppn,
+                base_size: user_sp,
+                heap_bottom: user_sp,
+                program_brk: user_sp,
+                task_cx: TaskContext::goto_trap_return(kernel_stack_top),
+                task_status: TaskStatus::Ready,
+                memory_set,
+                parent: None,
+                children: Vec::new(),
+                exit_code: 0,
+                fd_table: vec![
+                    // 0 -> stdin
+                    Some(Arc::new(Stdin)),
+                    // 1 -> stdout
+                    Some(Arc::new(Stdout)),
+                    // 2 -> stderr
+                    Some(Arc::new(Stdout)),
+                ],
+                signals: SignalFlags::empty(),
+                signal_mask: SignalFlags::empty(),
+                signal_actions: SignalActions::default(),
+                frozen: false,
+                trap_ctx_backup: None,
+                signal_mask_backup: SignalFlags::empty(),
+                stride: BIG_STRIDE / DEFAULT_PRIORITY,
+                pass: 0,
+                vruntime: 0,
+            }),
         };
         // prepare TrapContext in user space
         let trap_cx = task_control_block.inner_exclusive_access().get_trap_cx();
@@ -105,36 +164,68 @@ impl TaskControlBlock {
         );
         task_control_block
     }
-    pub fn exec(&self, elf_data: &[u8]) {
+    /// Must be called on the current task.
+    /// Return `ETXTBSY` and keep the current program if the elf is open for writing.
+    pub fn exec(
+        &self,
+        elf_inode: &Arc<Inode>,
+        args: Vec<String>,
+        envs: Vec<String>,
+    ) -> Result<(), Errno> {
         // memory_set with elf program headers/trampoline/trap context/user stack
-        let (memory_set, user_sp, entry_point) = MemorySet::from_elf(elf_data);
+        let (memory_set, user_sp, entry_point) = MemorySet::from_elf(elf_inode)?;
         let trap_cx_ppn = memory_set
             .translate(VirtAddr::from(TRAP_CONTEXT).into())
             .unwrap()
             .ppn();
+        let token = memory_set.token();
 
         // **** access current TCB exclusively
         let mut inner = self.inner_exclusive_access();
         // substitute memory_set
         inner.memory_set = memory_set;
+        // this hart runs the new space from now on, so other harts leave its pages alone
+        set_running_token(token);
         // update trap_cx ppn
         inner.trap_cx_ppn = trap_cx_ppn;
+        // the new heap is empty, right above the new user stack
+        inner.heap_bottom = user_sp;
+        inner.program_brk = user_sp;
+        // handlers of the old program are gone, pending and blocked signals are kept
+        inner.signal_actions = SignalActions::default();
+        if inner.trap_ctx_backup.take().is_some() {
+            inner.signal_mask = inner.signal_mask_backup;
+        }
+        drop(inner);
+        // **** release current PCB
+
+        // push argv and envp onto the new user stack,
+        // whose pages are mapped on first touch through the current task
+        let (user_sp, argv_base, envp_base) = push_args(token, user_sp, &args, &envs);
+
+        // **** access current TCB exclusively
+        let mut inner = self.inner_exclusive_access();
         // initialize trap_cx
-        let trap_cx = TrapContext::app_init_context(
+        let mut trap_cx = TrapContext::app_init_context(
             entry_point,
             user_sp,
             KERNEL_SPACE.exclusive_access().token(),
             self.kernel_stack.get_top(),
             trap_handler as usize,
         );
+        // main(argc, argv, envp)
+        trap_cx.x[10] = args.len();
+        trap_cx.x[11] = argv_base;
+        trap_cx.x[12] = envp_base;
         *inner.get_trap_cx() = trap_cx;
         // **** release current PCB
+        Ok(())
     }
     pub fn fork(self: &Arc<TaskControlBlock>) -> Arc<TaskControlBlock> {
         // ---- hold parent PCB lock
         let mut parent_inner = self.inner_exclusive_access();
         // copy user space(include trap context)
-        let memory_set = MemorySet::from_existed_user(&parent_inner.memory_set);
+        let memory_set = MemorySet::from_existed_user(&mut parent_inner.memory_set);
         let trap_cx_ppn = memory_set
             .translate(VirtAddr::from(TRAP_CONTEXT).into())
             .unwrap()
@@ -155,19 +246,31 @@ impl TaskControlBlock {
         let task_control_block = Arc::new(TaskControlBlock {
             pid: pid_handle,
             kernel_stack,
-            inner: unsafe {
-                UPSafeCell::new(TaskControlBlockInner {
-                    trap_cx_ppn,
-                    base_size: parent_inner.base_size,
-                    task_cx: TaskContext::goto_trap_return(kernel_stack_top),
-                    task_status: TaskStatus::Ready,
-                    memory_set,
-                    parent: Some(Arc::downgrade(self)),
-                    children: Vec::new(),
-                    exit_code: 0,
-                    fd_table: new_fd_table,
-                })
-            },
+            inner: SpinLock::new(TaskControlBlockInner {
+                trap_cx_ppn,
+                base_size: parent_inner.base_size,
+                heap_bottom: parent_inner.heap_bottom,
+                program_brk: parent_inner.program_brk,
+                task_cx: TaskContext::goto_trap_return(kernel_stack_top),
+                task_status: TaskStatus::Ready,
+                memory_set,
+                parent: Some(Arc::downgrade(self)),
+                children: Vec::new(),
+                exit_code: 0,
+                fd_table: new_fd_table,
+                // pending signals are not inherited
+                signals: SignalFlags::empty(),
+                signal_mask: parent_inner.signal_mask,
+                signal_actions: parent_inner.signal_actions.clone(),
+                frozen: false,
+                trap_ctx_backup: parent_inner.trap_ctx_backup,
+                signal_mask_backup: parent_inner.signal_mask_backup,
+                // starting from the pass of the parent, the child does not
+                // take over the CPU to catch up with the others
+                stride: parent_inner.stride,
+                pass: parent_inner.pass,
+                vruntime: parent_inner.vruntime,
+            }),
         });
         // add child
         parent_inner.children.push(task_control_block.clone());
@@ -185,9 +288,53 @@ impl TaskControlBlock {
     }
 }
 
+/// Copy `args` and `envs` below `user_sp` in the address space of `token`, as two
+/// NULL-terminated arrays of pointers followed by the strings they point to.
+/// Return the new aligned `user_sp` and the addresses of the two arrays.
+fn push_args(
+    token: usize,
+    user_sp: usize,
+    args: &[String],
+    envs: &[String],
+) -> (usize, usize, usize) {
+    let strings_len: usize = args.iter().chain(envs).map(|s| s.len() + 1).sum();
+    let arrays_len = (args.len() + envs.len() + 2) * size_of::<usize>();
+    // make the user_sp aligned to 8B for k210 platform
+    let base = (user_sp - strings_len - arrays_len) & !(size_of::<usize>() - 1);
+    // build the whole block here, so that it is written to user memory at once
+    let mut block = Vec::with_capacity(user_sp - base);
+    let mut string_ptr = base + arrays_len;
+    for strings in [args, envs] {
+        for string in strings {
+            block.extend_from_slice(&string_ptr.to_ne_bytes());
+            string_ptr += string.len() + 1;
+        }
+        block.extend_from_slice(&0usize.to_ne_bytes());
+    }
+    for string in args.iter().chain(envs) {
+        block.extend_from_slice(string.as_bytes());
+        block.push(0);
+    }
+    block.resize(user_sp - base, 0);
+    // the new user stack has room for them, checked by `sys_exec`,
+    // and its frames are pinned until they are written
+    let mut buffer = UserBuffer::new(
+        translated_byte_buffer(token, base as *const u8, block.len(), true).unwrap(),
+    );
+    let mut offset = 0;
+    for slice in buffer.buffers.iter_mut() {
+        slice.copy_from_slice(&block[offset..offset + slice.len()]);
+        offset += slice.len();
+    }
+    let envp_base = base + (args.len() + 1) * size_of::<usize>();
+    (base, base, envp_base)
+}
+
 #[derive(Copy, Clone, PartialEq)]
 pub enum TaskStatus {
     Ready,
     Running,
+    /// waiting to be woken up by [`wakeup_task`](super::wakeup_task), not in the ready queue
+    Blocked,
     Zombie,
 }
@@ -1,11 +1,44 @@
 //! RISC-V timer-related functionality
 
 use crate::config::CLOCK_FREQ;
+use crate::drivers::rtc_time_ns;
 use crate::sbi::set_timer;
+use core::sync::atomic::{AtomicUsize, Ordering};
 use riscv::register::time;
 
 const TICKS_PER_SEC: usize = 100;
 const MSEC_PER_SEC: usize = 1000;
+const NSEC_PER_SEC: usize = 1_000_000_000;
+
+/// A span of time, or a point of time since some epoch, as in `struct timespec`
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TimeSpec {
+    /// seconds
+    pub tv_sec: usize,
+    /// nanoseconds, less than a second
+    pub tv_nsec: usize,
+}
+
+impl TimeSpec {
+    /// The span of `ns` nanoseconds
+    pub fn from_ns(ns: usize) -> Self {
+        Self {
+            tv_sec: ns / NSEC_PER_SEC,
+            tv_nsec: ns % NSEC_PER_SEC,
+        }
+    }
+    /// Ticks of the `time` CSR in the span, rounded up. `None` if it is malformed.
+    pub fn to_ticks(&self) -> Option<usize> {
+        if self.tv_nsec >= NSEC_PER_SEC {
+            return None;
+        }
+        let nsec_ticks = (self.tv_nsec * CLOCK_FREQ + NSEC_PER_SEC - 1) / NSEC_PER_SEC;
+        self.tv_sec
+            .checked_mul(CLOCK_FREQ)
+            .and_then(|ticks| ticks.checked_add(nsec_ticks))
+    }
+}
 ///get current time
 pub fn get_time() -> usize {
     time::read()
@@ -14,6 +47,27 @@ pub fn get_time() -> usize {
 pub fn get_time_ms() -> usize {
     time::read() / (CLOCK_FREQ / MSEC_PER_SEC)
 }
+/// get current time in nanoseconds, the `time` CSR counts from boot
+pub fn get_time_ns() -> usize {
+    let ticks = time::read();
+    // without overflowing, as `ticks * NSEC_PER_SEC` would after 20 minutes
+    ticks / CLOCK_FREQ * NSEC_PER_SEC + ticks % CLOCK_FREQ * NSEC_PER_SEC / CLOCK_FREQ
+}
+
+/// nanoseconds since the Unix epoch when `get_time_ns` was 0, set by [`init`]
+static BOOT_REALTIME_NS: AtomicUsize = AtomicUsize::new(0);
+
+/// read the wall-clock time from the RTC once, it is counted by the `time` CSR from then on
+pub fn init() {
+    let boot_ns = (rtc_time_ns() as usize).saturating_sub(get_time_ns());
+    BOOT_REALTIME_NS.store(boot_ns, Ordering::Relaxed);
+}
+
+/// get wall-clock time in nanoseconds since the Unix epoch
+pub fn get_realtime_ns() -> usize {
+    BOOT_REALTIME_NS.load(Ordering::Relaxed) + get_time_ns()
+}
+
 /// set the next timer interrupt
 pub fn set_next_trigger() {
     set_timer(get_time() + CLOCK_FREQ / TICKS_PER_SEC);
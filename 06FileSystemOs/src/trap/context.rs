@@ -2,7 +2,7 @@
 use riscv::register::sstatus::{self, Sstatus, SPP};
 
 #[repr(C)]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 ///trap context structure containing sstatus, sepc and registers
 pub struct TrapContext {
     /// general regs[0..31]
@@ -17,6 +17,8 @@ pub struct TrapContext {
     pub kernel_sp: usize,
     /// Addr of trap_handler function
     pub trap_handler: usize,
+    /// tp of the kernel, the hartid of the hart that last returned to user space
+    pub kernel_tp: usize,
 }
 
 impl TrapContext {
@@ -42,6 +44,7 @@ impl TrapContext {
             kernel_satp,
             kernel_sp,
             trap_handler,
+            kernel_tp: 0,
         };
         cx.set_sp(sp);
         cx
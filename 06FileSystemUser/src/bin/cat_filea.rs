@@ -5,23 +5,23 @@
 extern crate user_lib;
 extern crate alloc;
 
-use user_lib::{close, open, read, OpenFlags};
+use alloc::vec;
+use user_lib::{close, fstat, open, read, strerror, OpenFlags, Stat};
 
 #[no_mangle]
 pub fn main() -> i32 {
     let fd = open("filea\0", OpenFlags::RDONLY);
-    if fd == -1 {
-        panic!("Error occured when opening file");
+    if fd < 0 {
+        panic!("Error occured when opening file: {}", strerror(fd));
     }
     let fd = fd as usize;
-    let mut buf = [0u8; 256];
-    loop {
-        let size = read(fd, &mut buf) as usize;
-        if size == 0 {
-            break;
-        }
-        println!("{}", core::str::from_utf8(&buf[..size]).unwrap());
-    }
+    let mut stat = Stat::new();
+    assert_eq!(fstat(fd, &mut stat), 0);
+    // read the whole file at once
+    let mut buf = vec![0u8; stat.size as usize];
+    let size = read(fd, &mut buf) as usize;
+    assert_eq!(size, buf.len());
+    println!("{}", core::str::from_utf8(&buf[..size]).unwrap());
     close(fd);
     0
 }
@@ -0,0 +1,32 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{clock_gettime, sleep, Errno, TimeSpec, CLOCK_MONOTONIC, CLOCK_REALTIME};
+
+/// 2020-01-01T00:00:00Z, any RTC should be past it
+const EPOCH_2020: usize = 1_577_836_800;
+
+fn ns(ts: &TimeSpec) -> usize {
+    ts.tv_sec * 1_000_000_000 + ts.tv_nsec
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut start = TimeSpec::default();
+    let mut end = TimeSpec::default();
+    assert_eq!(clock_gettime(CLOCK_MONOTONIC, &mut start), 0);
+    sleep(100);
+    assert_eq!(clock_gettime(CLOCK_MONOTONIC, &mut end), 0);
+    assert!(start.tv_nsec < 1_000_000_000 && end.tv_nsec < 1_000_000_000);
+    assert!(ns(&end) - ns(&start) >= 100_000_000);
+    let mut now = TimeSpec::default();
+    assert_eq!(clock_gettime(CLOCK_REALTIME, &mut now), 0);
+    assert!(now.tv_sec >= EPOCH_2020);
+    println!("realtime: {}.{:09}", now.tv_sec, now.tv_nsec);
+    assert_eq!(clock_gettime(42, &mut now), Errno::EINVAL.ret());
+    println!("clocktest passed!");
+    0
+}
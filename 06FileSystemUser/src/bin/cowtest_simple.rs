@@ -0,0 +1,48 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, fork, pipe, read, waitpid, write};
+
+const LEN: usize = 4096 * 4;
+static mut DATA: [u8; LEN] = [1u8; LEN];
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut pipe_fd = [0usize; 2];
+    pipe(&mut pipe_fd);
+    let pid = fork();
+    if pid == 0 {
+        // stores from user mode
+        unsafe {
+            for b in DATA[..LEN / 2].iter_mut() {
+                *b = 2;
+            }
+            assert!(DATA[..LEN / 2].iter().all(|&b| b == 2));
+        }
+        // stores from the kernel into a shared page
+        close(pipe_fd[1]);
+        unsafe {
+            assert_eq!(read(pipe_fd[0], &mut DATA[LEN / 2..LEN / 2 + 5]), 5);
+            assert_eq!(&DATA[LEN / 2..LEN / 2 + 5], b"hello");
+        }
+        close(pipe_fd[0]);
+        return 0;
+    }
+    close(pipe_fd[0]);
+    assert_eq!(write(pipe_fd[1], b"hello"), 5);
+    close(pipe_fd[1]);
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    // the child's writes are not seen, and the parent can still write
+    unsafe {
+        assert!(DATA.iter().all(|&b| b == 1));
+        DATA[0] = 3;
+        assert_eq!(DATA[0], 3);
+    }
+    println!("cow_test passed!");
+    0
+}
@@ -0,0 +1,50 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, mkdir, open, read, rmdir, unlink, write, Errno, OpenFlags};
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(mkdir("dira\0"), 0);
+    assert_eq!(mkdir("dira/dirb\0"), 0);
+    // already exists
+    assert_eq!(mkdir("dira\0"), Errno::EEXIST.ret());
+    // parent does not exist
+    assert_eq!(mkdir("dirc/dird\0"), Errno::ENOENT.ret());
+
+    let test_str = "Hello, directory!";
+    let path = "dira/dirb/../dirb/./filea\0";
+    let fd = open(path, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    write(fd as usize, test_str.as_bytes());
+    close(fd as usize);
+
+    let fd = open("/dira/dirb/filea\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let mut buffer = [0u8; 100];
+    let read_len = read(fd as usize, &mut buffer) as usize;
+    close(fd as usize);
+    assert_eq!(test_str, core::str::from_utf8(&buffer[..read_len]).unwrap());
+
+    // not empty
+    assert_eq!(rmdir("dira\0"), Errno::ENOTEMPTY.ret());
+    // not a directory
+    assert_eq!(rmdir("dira/dirb/filea\0"), Errno::ENOTDIR.ret());
+    assert_eq!(rmdir("dira/dirb/..\0"), Errno::EINVAL.ret());
+    // not a file
+    assert_eq!(unlink("dira/dirb\0"), Errno::EISDIR.ret());
+
+    assert_eq!(unlink("dira/dirb/filea\0"), 0);
+    assert_eq!(
+        open("dira/dirb/filea\0", OpenFlags::RDONLY),
+        Errno::ENOENT.ret()
+    );
+    assert_eq!(rmdir("dira/dirb\0"), 0);
+    assert_eq!(rmdir("dira\0"), 0);
+    assert_eq!(rmdir("dira\0"), Errno::ENOENT.ret());
+    println!("dir_test passed!");
+    0
+}
@@ -0,0 +1,50 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, dup, dup3, open, read, unlink, Errno, OpenFlags};
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let path = "dupa\0";
+    // save stdout to restore it later
+    let stdout = dup(1);
+    assert!(stdout > 2);
+    assert_eq!(dup(100), Errno::EBADF.ret());
+    assert_eq!(dup3(1, 1, 0), Errno::EINVAL.ret());
+    assert_eq!(dup3(100, 1, 0), Errno::EBADF.ret());
+    // far beyond the fd limit
+    assert_eq!(dup3(1, usize::MAX, 0), Errno::EBADF.ret());
+    assert_eq!(dup3(1, 1 << 30, 0), Errno::EBADF.ret());
+
+    // print into a file instead of the console
+    let fd = open(
+        path,
+        OpenFlags::CREATE | OpenFlags::TRUNC | OpenFlags::WRONLY,
+    );
+    assert!(fd > 0);
+    assert_eq!(dup3(fd as usize, 1, 0), 1);
+    close(fd as usize);
+    print!("Hello, world!");
+    assert_eq!(dup3(stdout as usize, 1, 0), 1);
+    close(stdout as usize);
+
+    // both descriptors share the offset
+    let fd = open(path, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let fd2 = dup(fd as usize);
+    assert!(fd2 > fd);
+    let mut buffer = [0u8; 5];
+    assert_eq!(read(fd as usize, &mut buffer), 5);
+    assert_eq!(&buffer, b"Hello");
+    assert_eq!(read(fd2 as usize, &mut buffer), 5);
+    assert_eq!(&buffer, b", wor");
+    close(fd as usize);
+    close(fd2 as usize);
+
+    assert_eq!(unlink(path), 0);
+    println!("dup_test passed!");
+    0
+}
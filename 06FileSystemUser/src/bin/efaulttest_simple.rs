@@ -0,0 +1,80 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec;
+use user_lib::{
+    close, exec, exit, fork, mmap, munmap, open, pipe, read, waitpid, write, Errno, OpenFlags,
+    MAP_ANONYMOUS, MAP_PRIVATE, PROT_READ, PROT_WRITE,
+};
+
+const PAGE_SIZE: usize = 4096;
+
+fn bytes(addr: usize, len: usize) -> &'static mut [u8] {
+    unsafe { core::slice::from_raw_parts_mut(addr as *mut u8, len) }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // nothing is mapped near address 0
+    assert_eq!(write(1, bytes(0x10, 8)), Errno::EFAULT.ret());
+    let path = unsafe { core::str::from_utf8_unchecked(bytes(0x10, 1)) };
+    assert_eq!(open(path, OpenFlags::RDONLY), Errno::EFAULT.ret());
+    // kernel memory is not accessible to user mode
+    assert_eq!(
+        write(1, bytes(0xffff_ffc0_8020_0000, 8)),
+        Errno::EFAULT.ret()
+    );
+    // the code is not writable
+    let text = main as usize & !(PAGE_SIZE - 1);
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    assert_eq!(write(fds[1], b"test"), 4);
+    assert_eq!(read(fds[0], bytes(text, 4)), Errno::EFAULT.ret());
+    close(fds[0]);
+    close(fds[1]);
+    let fds = unsafe { core::slice::from_raw_parts_mut(text as *mut usize, 2) };
+    assert_eq!(pipe(fds), Errno::EFAULT.ret());
+    // unmapped pages can not be accessed either
+    let addr = mmap(
+        0,
+        PAGE_SIZE * 2,
+        PROT_READ | PROT_WRITE,
+        MAP_PRIVATE | MAP_ANONYMOUS,
+        0,
+        0,
+    ) as usize;
+    assert_eq!(munmap(addr + PAGE_SIZE, PAGE_SIZE), 0);
+    assert_eq!(
+        write(1, bytes(addr + PAGE_SIZE - 4, 8)),
+        Errno::EFAULT.ret()
+    );
+    // a child is kept if its exit code can not be stored
+    let pid = fork();
+    if pid == 0 {
+        exit(7);
+    }
+    let exit_code = unsafe { &mut *(text as *mut i32) };
+    assert_eq!(waitpid(pid as usize, exit_code), Errno::EFAULT.ret());
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 7);
+    // the trap context page is mapped without U, so a store to it is a fault
+    let pid = fork();
+    if pid == 0 {
+        let trap_context = usize::MAX - 2 * PAGE_SIZE + 1;
+        bytes(trap_context, 1)[0] = 0;
+        exit(0);
+    }
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, -11);
+    // exec fails as soon as the arguments outgrow the new user stack
+    let mut args = vec!["\0".as_ptr(); 10000];
+    args.push(core::ptr::null());
+    assert_eq!(exec("hello_world\0", &args), Errno::E2BIG.ret());
+    println!("efault_test passed!");
+    0
+}
@@ -0,0 +1,55 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, exec, open, pipe, read, unlink, write, Errno, OpenFlags};
+
+const PAGE_SIZE: usize = 4096;
+/// far more than the frames we touch, so it only costs anything once used
+const LEN: usize = 512 * PAGE_SIZE;
+static mut BSS: [u8; LEN] = [0u8; LEN];
+
+#[no_mangle]
+pub fn main() -> i32 {
+    unsafe {
+        // first touch from user mode, by a load and by a store
+        assert_eq!(BSS[LEN - 1], 0);
+        BSS[LEN / 2] = 1;
+        assert_eq!(BSS[LEN / 2], 1);
+        // first touch by the kernel, across a page boundary
+        let mut pipe_fd = [0usize; 2];
+        pipe(&mut pipe_fd);
+        assert_eq!(write(pipe_fd[1], b"hello"), 5);
+        close(pipe_fd[1]);
+        let start = 10 * PAGE_SIZE - 2;
+        assert_eq!(read(pipe_fd[0], &mut BSS[start..start + 5]), 5);
+        assert_eq!(&BSS[start..start + 5], b"hello");
+        close(pipe_fd[0]);
+    }
+    // a running program is read on first touch, so it can not be written
+    assert_eq!(
+        open("lazytest_simple\0", OpenFlags::WRONLY),
+        Errno::ETXTBSY.ret()
+    );
+    // nor run while open for writing
+    let fd = open("hello_world\0", OpenFlags::RDWR);
+    assert!(fd >= 0);
+    assert_eq!(
+        exec("hello_world\0", &[core::ptr::null::<u8>()]),
+        Errno::ETXTBSY.ret()
+    );
+    close(fd as usize);
+    // only elf files can be run
+    let args = [core::ptr::null::<u8>()];
+    assert_eq!(exec("/\0", &args), Errno::EACCES.ret());
+    let fd = open("notelf\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd >= 0);
+    assert_eq!(write(fd as usize, b"#!/bin/sh\n"), 10);
+    close(fd as usize);
+    assert_eq!(exec("notelf\0", &args), Errno::ENOEXEC.ret());
+    assert_eq!(unlink("notelf\0"), 0);
+    println!("lazy_test passed!");
+    0
+}
@@ -0,0 +1,56 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, link, mkdir, open, read, rmdir, unlink, write, Errno, OpenFlags};
+
+fn read_str(path: &str, buffer: &mut [u8]) -> usize {
+    let fd = open(path, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let read_len = read(fd as usize, buffer) as usize;
+    close(fd as usize);
+    read_len
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let test_str = "Hello, link!";
+    let fd = open("linka\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    write(fd as usize, test_str.as_bytes());
+    close(fd as usize);
+
+    assert_eq!(mkdir("linkdir\0"), 0);
+    assert_eq!(link("linka\0", "linkdir/linkb\0"), 0);
+    // already exists
+    assert_eq!(link("linka\0", "linkdir/linkb\0"), Errno::EEXIST.ret());
+    // directories can not be linked
+    assert_eq!(link("linkdir\0", "linkc\0"), Errno::EPERM.ret());
+
+    // the data survives until the last link is removed
+    let mut buffer = [0u8; 100];
+    assert_eq!(unlink("linka\0"), 0);
+    let read_len = read_str("linkdir/linkb\0", &mut buffer);
+    assert_eq!(test_str, core::str::from_utf8(&buffer[..read_len]).unwrap());
+    // and an open file stays readable after its last link is removed
+    let fd = open("linkdir/linkb\0", OpenFlags::RDONLY);
+    assert!(fd > 0);
+    assert_eq!(unlink("linkdir/linkb\0"), 0);
+    assert_eq!(
+        open("linkdir/linkb\0", OpenFlags::RDONLY),
+        Errno::ENOENT.ret()
+    );
+    let fd2 = open("linkd\0", OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd2 > 0);
+    write(fd2 as usize, &[b'x'; 512]);
+    close(fd2 as usize);
+    let read_len = read(fd as usize, &mut buffer) as usize;
+    assert_eq!(test_str, core::str::from_utf8(&buffer[..read_len]).unwrap());
+    close(fd as usize);
+    assert_eq!(unlink("linkd\0"), 0);
+    assert_eq!(rmdir("linkdir\0"), 0);
+    println!("link_test passed!");
+    0
+}
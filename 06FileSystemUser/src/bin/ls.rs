@@ -0,0 +1,46 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::string::String;
+use alloc::vec;
+use user_lib::{close, fstat, open, read, strerror, OpenFlags, Stat, StatMode};
+
+/// Size of a directory entry on disk: a 28-byte name and a u32 inode number
+const DIRENT_SZ: usize = 32;
+const NAME_LENGTH: usize = 28;
+
+/// List the directory given as the argument, the root directory by default.
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    let mut path = String::from(if argc > 1 { argv[1] } else { "/" });
+    path.push('\0');
+    let fd = open(path.as_str(), OpenFlags::RDONLY | OpenFlags::DIRECTORY);
+    if fd < 0 {
+        println!(
+            "ls: cannot open {}: {}",
+            path.trim_end_matches('\0'),
+            strerror(fd)
+        );
+        return -1;
+    }
+    let fd = fd as usize;
+    let mut stat = Stat::new();
+    assert_eq!(fstat(fd, &mut stat), 0);
+    assert!(stat.mode.contains(StatMode::DIR));
+    let mut buf = vec![0u8; stat.size as usize];
+    let size = read(fd, &mut buf) as usize;
+    close(fd);
+    for dirent in buf[..size].chunks_exact(DIRENT_SZ) {
+        let name = &dirent[..NAME_LENGTH];
+        let len = name.iter().position(|&c| c == 0).unwrap_or(NAME_LENGTH);
+        // skip the free slots of removed files
+        if len > 0 {
+            println!("{}", core::str::from_utf8(&name[..len]).unwrap());
+        }
+    }
+    0
+}
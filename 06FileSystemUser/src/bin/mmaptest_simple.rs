@@ -0,0 +1,129 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, fork, mmap, msync, munmap, open, read, waitpid, write, Errno, OpenFlags, MAP_ANONYMOUS,
+    MAP_FIXED, MAP_PRIVATE, MAP_SHARED, MS_SYNC, PROT_READ, PROT_WRITE,
+};
+
+const PAGE_SIZE: usize = 4096;
+
+fn page(addr: usize, i: usize) -> &'static mut [u8] {
+    unsafe { core::slice::from_raw_parts_mut((addr + i * PAGE_SIZE) as *mut u8, PAGE_SIZE) }
+}
+
+fn anonymous() {
+    let addr = mmap(
+        0,
+        PAGE_SIZE * 3,
+        PROT_READ | PROT_WRITE,
+        MAP_PRIVATE | MAP_ANONYMOUS,
+        0,
+        0,
+    );
+    assert!(addr > 0);
+    let addr = addr as usize;
+    assert!(page(addr, 1).iter().all(|&b| b == 0));
+    for i in 0..3 {
+        page(addr, i).fill(i as u8 + 1);
+    }
+    // taken pages can not be mapped again
+    assert_eq!(
+        mmap(
+            addr + PAGE_SIZE,
+            PAGE_SIZE,
+            PROT_READ,
+            MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED,
+            0,
+            0,
+        ),
+        Errno::EEXIST.ret()
+    );
+    // a child has its own copy
+    let pid = fork();
+    if pid == 0 {
+        page(addr, 0).fill(9);
+        user_lib::exit(0);
+    }
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    assert!(page(addr, 0).iter().all(|&b| b == 1));
+    // unmap the middle page only
+    assert_eq!(munmap(addr + PAGE_SIZE, PAGE_SIZE), 0);
+    assert!(page(addr, 2).iter().all(|&b| b == 3));
+    let pid = fork();
+    if pid == 0 {
+        page(addr, 1)[0] = 1;
+        user_lib::exit(0);
+    }
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, -11);
+    assert_eq!(munmap(addr, PAGE_SIZE * 3), 0);
+}
+
+fn shared_anonymous() {
+    let addr = mmap(
+        0,
+        PAGE_SIZE * 2,
+        PROT_READ | PROT_WRITE,
+        MAP_SHARED | MAP_ANONYMOUS,
+        0,
+        0,
+    );
+    assert!(addr > 0);
+    let addr = addr as usize;
+    // the parent sees what the child writes, also to pages untouched at fork
+    page(addr, 0)[0] = 1;
+    let pid = fork();
+    if pid == 0 {
+        page(addr, 0)[0] = 2;
+        page(addr, 1)[0] = 3;
+        user_lib::exit(0);
+    }
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    assert_eq!(page(addr, 0)[0], 2);
+    assert_eq!(page(addr, 1)[0], 3);
+    assert_eq!(munmap(addr, PAGE_SIZE * 2), 0);
+}
+
+fn shared_file() {
+    let fd = open(
+        "mmapfile\0",
+        OpenFlags::CREATE | OpenFlags::RDWR | OpenFlags::TRUNC,
+    );
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(write(fd, b"hello, mmap!"), 12);
+    let addr = mmap(0, PAGE_SIZE, PROT_READ | PROT_WRITE, MAP_SHARED, fd, 0);
+    assert!(addr > 0);
+    let addr = addr as usize;
+    let data = page(addr, 0);
+    assert_eq!(&data[..12], b"hello, mmap!");
+    assert!(data[12..].iter().all(|&b| b == 0));
+    data[..5].copy_from_slice(b"HELLO");
+    assert_eq!(msync(addr, PAGE_SIZE, MS_SYNC), 0);
+    data[7..11].copy_from_slice(b"MMAP");
+    assert_eq!(munmap(addr, PAGE_SIZE), 0);
+    close(fd);
+    let fd = open("mmapfile\0", OpenFlags::RDONLY) as usize;
+    let mut buf = [0u8; 32];
+    // pages past the end of file do not grow it
+    assert_eq!(read(fd, &mut buf), 12);
+    assert_eq!(&buf[..12], b"HELLO, MMAP!");
+    close(fd);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    anonymous();
+    shared_anonymous();
+    shared_file();
+    println!("mmap_test passed!");
+    0
+}
@@ -0,0 +1,73 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, mkdir, open, pwrite, read, rmdir, unlink, write, Errno, OpenFlags};
+
+fn read_all(path: &str, buffer: &mut [u8]) -> usize {
+    let fd = open(path, OpenFlags::RDONLY);
+    assert!(fd > 0);
+    let read_len = read(fd as usize, buffer) as usize;
+    close(fd as usize);
+    read_len
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let path = "opena\0";
+    let fd = open(
+        path,
+        OpenFlags::CREATE | OpenFlags::EXCL | OpenFlags::WRONLY,
+    );
+    assert!(fd > 0);
+    write(fd as usize, b"Hello");
+    close(fd as usize);
+    // EXCL fails on an existing file
+    assert_eq!(
+        open(
+            path,
+            OpenFlags::CREATE | OpenFlags::EXCL | OpenFlags::WRONLY
+        ),
+        Errno::EEXIST.ret()
+    );
+
+    // CREATE alone keeps the content
+    let fd = open(path, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd > 0);
+    close(fd as usize);
+    let mut buffer = [0u8; 100];
+    assert_eq!(read_all(path, &mut buffer), 5);
+
+    // every write goes to the end with APPEND
+    let fd = open(path, OpenFlags::WRONLY | OpenFlags::APPEND);
+    assert!(fd > 0);
+    write(fd as usize, b", world");
+    pwrite(fd as usize, b"!", 0);
+    close(fd as usize);
+    let read_len = read_all(path, &mut buffer);
+    assert_eq!(&buffer[..read_len], b"Hello, world!");
+
+    // TRUNC clears it
+    let fd = open(path, OpenFlags::WRONLY | OpenFlags::TRUNC);
+    assert!(fd > 0);
+    close(fd as usize);
+    assert_eq!(read_all(path, &mut buffer), 0);
+
+    // DIRECTORY only opens directories
+    assert_eq!(
+        open(path, OpenFlags::RDONLY | OpenFlags::DIRECTORY),
+        Errno::ENOTDIR.ret()
+    );
+    assert_eq!(mkdir("opendir\0"), 0);
+    let fd = open("opendir\0", OpenFlags::RDONLY | OpenFlags::DIRECTORY);
+    assert!(fd > 0);
+    close(fd as usize);
+    assert_eq!(open("opendir\0", OpenFlags::WRONLY), Errno::EISDIR.ret());
+
+    assert_eq!(rmdir("opendir\0"), 0);
+    assert_eq!(unlink(path), 0);
+    println!("open_test passed!");
+    0
+}
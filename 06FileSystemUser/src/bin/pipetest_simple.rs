@@ -0,0 +1,59 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{close, fork, kill, pipe, read, wait, waitpid, write, SIGINT};
+
+static STR: &str = "Hello, world!";
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // create pipe
+    let mut pipe_fd = [0usize; 2];
+    pipe(&mut pipe_fd);
+    // read end
+    assert_eq!(pipe_fd[0], 3);
+    // write end
+    assert_eq!(pipe_fd[1], 4);
+    if fork() == 0 {
+        // child process, read from parent
+        // close write_end
+        close(pipe_fd[1]);
+        let mut buffer = [0u8; 32];
+        let len_read = read(pipe_fd[0], &mut buffer) as usize;
+        assert_eq!(core::str::from_utf8(&buffer[..len_read]).unwrap(), STR);
+        // the parent has closed its write end
+        assert_eq!(read(pipe_fd[0], &mut buffer), 0);
+        // close read_end
+        close(pipe_fd[0]);
+        println!("Read OK, child process exited!");
+        0
+    } else {
+        // parent process, write to child
+        // close read end
+        close(pipe_fd[0]);
+        assert_eq!(write(pipe_fd[1], STR.as_bytes()), STR.len() as isize);
+        // close write end
+        close(pipe_fd[1]);
+        let mut child_exit_code: i32 = 0;
+        wait(&mut child_exit_code);
+        assert_eq!(child_exit_code, 0);
+        // a reader waiting for a writer that is still open can be interrupted
+        pipe(&mut pipe_fd);
+        let pid = fork();
+        if pid == 0 {
+            let mut buffer = [0u8; 32];
+            read(pipe_fd[0], &mut buffer);
+            unreachable!();
+        }
+        assert_eq!(kill(pid as usize, SIGINT), 0);
+        assert_eq!(waitpid(pid as usize, &mut child_exit_code), pid);
+        assert_eq!(child_exit_code, -SIGINT);
+        close(pipe_fd[0]);
+        close(pipe_fd[1]);
+        println!("pipe_test passed!");
+        0
+    }
+}
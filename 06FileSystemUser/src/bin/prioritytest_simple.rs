@@ -0,0 +1,39 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{exit, fork, get_time, set_priority, waitpid, Errno};
+
+/// Only checks the arguments and that no task starves. The share a priority
+/// buys is not measured: `set_priority` is a no-op under the default FIFO
+/// scheduler, and under stride or CFS the children only compete for a hart
+/// when there are fewer harts than children.
+#[no_mangle]
+pub fn main() -> i32 {
+    // priorities below 2 are rejected
+    assert_eq!(set_priority(0), Errno::EINVAL.ret());
+    assert_eq!(set_priority(1), Errno::EINVAL.ret());
+    assert_eq!(set_priority(-5), Errno::EINVAL.ret());
+    assert_eq!(set_priority(2), 2);
+    assert_eq!(set_priority(16), 16);
+    // every priority still gets its turn
+    let mut pids = [0isize; 3];
+    for (i, pid) in pids.iter_mut().enumerate() {
+        *pid = fork();
+        if *pid == 0 {
+            assert_eq!(set_priority(2 << (i * 2)), 2 << (i * 2));
+            let start = get_time();
+            while get_time() < start + 50 {}
+            exit(i as i32);
+        }
+    }
+    for (i, &pid) in pids.iter().enumerate() {
+        let mut exit_code = 0;
+        assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+        assert_eq!(exit_code, i as i32);
+    }
+    println!("priority_test passed!");
+    0
+}
@@ -0,0 +1,29 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+extern crate alloc;
+
+use alloc::string::String;
+use user_lib::{strerror, unlink};
+
+#[no_mangle]
+pub fn main(argc: usize, argv: &[&str]) -> i32 {
+    if argc < 2 {
+        println!("usage: rm FILE...");
+        return -1;
+    }
+    let mut ret = 0;
+    for arg in &argv[1..] {
+        let mut path = String::from(*arg);
+        path.push('\0');
+        let ret = unlink(path.as_str());
+        if ret != 0 {
+            println!("rm: cannot remove {}: {}", arg, strerror(ret));
+            ret = -1;
+        }
+    }
+    ret
+}
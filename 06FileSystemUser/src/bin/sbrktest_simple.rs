@@ -0,0 +1,57 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+extern crate alloc;
+
+use alloc::vec;
+use user_lib::{exit, fork, sbrk, waitpid, Errno};
+
+const PAGE_SIZE: usize = 0x1000;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // the heap grows far beyond its first increment
+    let v = vec![7u8; 1 << 20];
+    assert!(v.iter().all(|&b| b == 7));
+    drop(v);
+
+    let origin_brk = sbrk(0);
+    assert!(origin_brk > 0);
+    assert_eq!(sbrk(PAGE_SIZE as i32 * 4), origin_brk);
+    assert_eq!(sbrk(0), origin_brk + PAGE_SIZE as isize * 4);
+    let pages =
+        unsafe { core::slice::from_raw_parts_mut(origin_brk as usize as *mut u8, PAGE_SIZE * 4) };
+    pages.fill(1);
+    // a child inherits the heap
+    let pid = fork();
+    if pid == 0 {
+        assert_eq!(sbrk(0), origin_brk + PAGE_SIZE as isize * 4);
+        assert!(pages.iter().all(|&b| b == 1));
+        pages.fill(2);
+        exit(0);
+    }
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    assert!(pages.iter().all(|&b| b == 1));
+    // the break can not go below the bottom of the heap
+    assert_eq!(sbrk(i32::MIN), Errno::ENOMEM.ret());
+    assert_eq!(
+        sbrk(PAGE_SIZE as i32 * -4),
+        origin_brk + PAGE_SIZE as isize * 4
+    );
+    assert_eq!(sbrk(0), origin_brk);
+    // freed pages are unmapped
+    let pid = fork();
+    if pid == 0 {
+        pages[0] = 3;
+        exit(0);
+    }
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, -11);
+    println!("sbrk_test passed!");
+    0
+}
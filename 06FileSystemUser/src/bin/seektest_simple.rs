@@ -0,0 +1,47 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    close, fstat, lseek, open, pread, pwrite, read, unlink, write, Errno, OpenFlags, Stat,
+    SEEK_CUR, SEEK_END, SEEK_SET,
+};
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let fd = open("seeka\0", OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd > 0);
+    let fd = fd as usize;
+    assert_eq!(write(fd, b"Hello, world!"), 13);
+
+    // positional io does not move the offset
+    assert_eq!(pwrite(fd, b"W", 7), 1);
+    let mut buffer = [0u8; 5];
+    assert_eq!(pread(fd, &mut buffer, 7), 5);
+    assert_eq!(&buffer, b"World");
+    assert_eq!(lseek(fd, 0, SEEK_CUR), 13);
+
+    assert_eq!(lseek(fd, 0, SEEK_SET), 0);
+    assert_eq!(read(fd, &mut buffer), 5);
+    assert_eq!(&buffer, b"Hello");
+    assert_eq!(lseek(fd, -6, SEEK_END), 7);
+    assert_eq!(read(fd, &mut buffer), 5);
+    assert_eq!(&buffer, b"World");
+    assert_eq!(lseek(fd, -100, SEEK_CUR), Errno::EINVAL.ret());
+
+    // writing past the end leaves a hole of zeros
+    assert_eq!(lseek(fd, 100, SEEK_END), 113);
+    assert_eq!(write(fd, b"!"), 1);
+    let mut stat = Stat::new();
+    fstat(fd, &mut stat);
+    assert_eq!(stat.size, 114);
+    let mut hole = [0xffu8; 100];
+    assert_eq!(pread(fd, &mut hole, 13), 100);
+    assert!(hole.iter().all(|&b| b == 0));
+    close(fd);
+    assert_eq!(unlink("seeka\0"), 0);
+    println!("seek_test passed!");
+    0
+}
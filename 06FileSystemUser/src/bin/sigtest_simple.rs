@@ -0,0 +1,112 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{
+    exit, fork, getpid, kill, sigaction, sigprocmask, sigreturn, waitpid, yield_, Errno,
+    SignalAction, SignalFlags, SIGCHLD, SIGKILL, SIGSEGV, SIGUSR1, SIGUSR2, SIG_DFL, SIG_IGN,
+};
+
+static mut HANDLED: usize = 0;
+
+fn on_usr1(signum: i32) {
+    assert_eq!(signum, SIGUSR1);
+    unsafe {
+        HANDLED += 1;
+    }
+    sigreturn();
+}
+
+fn on_segv(signum: i32) {
+    exit(signum);
+}
+
+fn handled() -> usize {
+    unsafe { HANDLED }
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let pid = getpid() as usize;
+    let action = SignalAction {
+        handler: on_usr1 as usize,
+        mask: SignalFlags::empty(),
+    };
+    assert_eq!(sigaction(SIGUSR1, Some(&action), None), 0);
+    let mut old_action = SignalAction::default();
+    assert_eq!(sigaction(SIGUSR1, Some(&action), Some(&mut old_action)), 0);
+    assert_eq!(old_action.handler, on_usr1 as usize);
+    assert_eq!(sigaction(SIGKILL, Some(&action), None), Errno::EINVAL.ret());
+    assert_eq!(kill(pid, 0), Errno::EINVAL.ret());
+    assert_eq!(kill(pid, 32), Errno::EINVAL.ret());
+
+    // the handler runs before kill returns, and its return value is kept
+    assert_eq!(kill(pid, SIGUSR1), 0);
+    assert_eq!(handled(), 1);
+
+    // a blocked signal is delivered once unblocked
+    assert_eq!(sigprocmask(SignalFlags::SIGUSR1.bits()), 0);
+    assert_eq!(kill(pid, SIGUSR1), 0);
+    assert_eq!(handled(), 1);
+    assert_eq!(sigprocmask(0), SignalFlags::SIGUSR1.bits() as isize);
+    assert_eq!(handled(), 2);
+
+    let ignore = SignalAction {
+        handler: SIG_IGN,
+        mask: SignalFlags::empty(),
+    };
+    assert_eq!(sigaction(SIGUSR2, Some(&ignore), None), 0);
+    assert_eq!(kill(pid, SIGUSR2), 0);
+
+    // SIGKILL can not be blocked
+    let child = fork();
+    if child == 0 {
+        sigprocmask(SignalFlags::SIGKILL.bits());
+        loop {
+            yield_();
+        }
+    }
+    assert_eq!(kill(child as usize, SIGKILL), 0);
+    let mut exit_code: i32 = 0;
+    assert_eq!(waitpid(child as usize, &mut exit_code), child);
+    assert_eq!(exit_code, -9);
+    assert_eq!(kill(child as usize, SIGUSR1), Errno::ESRCH.ret());
+
+    // by default SIGCHLD is ignored and SIGUSR1 terminates
+    let default = SignalAction {
+        handler: SIG_DFL,
+        mask: SignalFlags::empty(),
+    };
+    assert_eq!(sigaction(SIGUSR1, Some(&default), None), 0);
+    let child = fork();
+    if child == 0 {
+        kill(getpid() as usize, SIGCHLD);
+        loop {
+            yield_();
+        }
+    }
+    assert_eq!(kill(child as usize, SIGUSR1), 0);
+    assert_eq!(waitpid(child as usize, &mut exit_code), child);
+    assert_eq!(exit_code, -SIGUSR1);
+
+    // a process handles its own fault
+    let child = fork();
+    if child == 0 {
+        let action = SignalAction {
+            handler: on_segv as usize,
+            mask: SignalFlags::empty(),
+        };
+        sigaction(SIGSEGV, Some(&action), None);
+        unsafe {
+            core::ptr::null_mut::<u8>().write_volatile(0);
+        }
+        unreachable!();
+    }
+    assert_eq!(waitpid(child as usize, &mut exit_code), child);
+    assert_eq!(exit_code, SIGSEGV);
+
+    println!("sig_test passed!");
+    0
+}
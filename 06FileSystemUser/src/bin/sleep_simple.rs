@@ -4,7 +4,7 @@
 #[macro_use]
 extern crate user_lib;
 
-use user_lib::{get_time, sleep};
+use user_lib::{get_time, nanosleep, sleep, Errno, TimeSpec};
 
 #[no_mangle]
 pub fn main() -> i32 {
@@ -18,6 +18,12 @@ pub fn main() -> i32 {
         end,
         end - start
     );
+    assert!(end - start >= 100);
+    let malformed = TimeSpec {
+        tv_sec: 0,
+        tv_nsec: 1_000_000_000,
+    };
+    assert_eq!(nanosleep(&malformed), Errno::EINVAL.ret());
     println!("r_sleep passed!");
     0
 }
@@ -0,0 +1,44 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{mmap, munmap, MAP_ANONYMOUS, MAP_PRIVATE, PROT_READ, PROT_WRITE};
+
+const PAGE_SIZE: usize = 4096;
+/// more than the physical memory of the qemu virt machine
+const LEN: usize = 136 << 20;
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let addr = mmap(
+        0,
+        LEN,
+        PROT_READ | PROT_WRITE,
+        MAP_PRIVATE | MAP_ANONYMOUS,
+        0,
+        0,
+    );
+    assert!(addr > 0);
+    let addr = addr as usize;
+    let words_per_page = PAGE_SIZE / core::mem::size_of::<usize>();
+    let memory = unsafe {
+        core::slice::from_raw_parts_mut(addr as *mut usize, LEN / core::mem::size_of::<usize>())
+    };
+    for (i, page) in memory.chunks_mut(words_per_page).enumerate() {
+        page[0] = i;
+        page[words_per_page - 1] = !i;
+        if i % 4096 == 0 {
+            println!("written {} MiB", i / 256);
+        }
+    }
+    // pages swapped out come back in on page fault
+    for (i, page) in memory.chunks(words_per_page).enumerate() {
+        assert_eq!(page[0], i);
+        assert_eq!(page[words_per_page - 1], !i);
+    }
+    assert_eq!(munmap(addr, LEN), 0);
+    println!("swap_test passed!");
+    0
+}
@@ -13,8 +13,153 @@ const DL: u8 = 0x7fu8;
 const BS: u8 = 0x08u8;
 
 use alloc::string::String;
+use alloc::vec::Vec;
 use user_lib::console::getchar;
-use user_lib::{exec, fork, waitpid};
+use user_lib::{close, dup3, exec, exit, fork, open, pipe, strerror, waitpid, OpenFlags};
+
+/// One command of a pipeline, with its file redirections.
+/// All strings are NUL-terminated.
+struct ProcessArguments {
+    input: Option<String>,
+    output: Option<String>,
+    append: bool,
+    /// `args[0]` is the program to run
+    args: Vec<String>,
+    /// pointers to `args` followed by a NULL, as `exec` expects
+    args_addr: Vec<*const u8>,
+}
+
+impl ProcessArguments {
+    /// Parse `name [args...] [< input] [> output | >> output]`,
+    /// with every token separated by whitespace.
+    fn parse(command: &str) -> Option<Self> {
+        let mut input = None;
+        let mut output = None;
+        let mut append = false;
+        let mut args = Vec::new();
+        let mut words = command.split_whitespace();
+        while let Some(word) = words.next() {
+            match word {
+                "<" => input = Some(with_nul(words.next()?)),
+                ">" | ">>" => {
+                    append = word == ">>";
+                    output = Some(with_nul(words.next()?));
+                }
+                _ => args.push(with_nul(word)),
+            }
+        }
+        if args.is_empty() {
+            return None;
+        }
+        let mut args_addr: Vec<*const u8> = args.iter().map(|arg| arg.as_ptr()).collect();
+        args_addr.push(core::ptr::null::<u8>());
+        Some(Self {
+            input,
+            output,
+            append,
+            args,
+            args_addr,
+        })
+    }
+}
+
+fn with_nul(s: &str) -> String {
+    let mut s = String::from(s);
+    s.push('\0');
+    s
+}
+
+/// Open `path` and move it onto `target_fd`.
+fn redirect(path: &str, flags: OpenFlags, target_fd: usize) {
+    let fd = open(path, flags);
+    if fd < 0 {
+        println!(
+            "Error when opening file {}: {}",
+            path.trim_end_matches('\0'),
+            strerror(fd)
+        );
+        exit(-4);
+    }
+    let fd = fd as usize;
+    dup3(fd, target_fd, 0);
+    close(fd);
+}
+
+fn run(line: &str) {
+    let commands: Vec<ProcessArguments> =
+        match line.split('|').map(ProcessArguments::parse).collect() {
+            Some(commands) => commands,
+            None => {
+                println!("Invalid command!");
+                return;
+            }
+        };
+    // only the ends of a pipeline can be redirected to files
+    let last = commands.len() - 1;
+    if commands.iter().enumerate().any(|(i, command)| {
+        (i != 0 && command.input.is_some()) || (i != last && command.output.is_some())
+    }) {
+        println!("Invalid command: redirection inside a pipeline!");
+        return;
+    }
+    // pipes[i] connects commands[i] to commands[i + 1]
+    let mut pipes: Vec<[usize; 2]> = Vec::new();
+    for _ in 0..last {
+        let mut pipe_fd = [0usize; 2];
+        pipe(&mut pipe_fd);
+        pipes.push(pipe_fd);
+    }
+    let mut children: Vec<isize> = Vec::new();
+    for (i, command) in commands.iter().enumerate() {
+        let pid = fork();
+        if pid == 0 {
+            // child process
+            if i > 0 {
+                dup3(pipes[i - 1][0], 0, 0);
+            }
+            if i < last {
+                dup3(pipes[i][1], 1, 0);
+            }
+            for pipe_fd in pipes.iter() {
+                close(pipe_fd[0]);
+                close(pipe_fd[1]);
+            }
+            if let Some(input) = &command.input {
+                redirect(input.as_str(), OpenFlags::RDONLY, 0);
+            }
+            if let Some(output) = &command.output {
+                let mode = if command.append {
+                    OpenFlags::APPEND
+                } else {
+                    OpenFlags::TRUNC
+                };
+                redirect(
+                    output.as_str(),
+                    OpenFlags::CREATE | OpenFlags::WRONLY | mode,
+                    1,
+                );
+            }
+            let ret = exec(command.args[0].as_str(), command.args_addr.as_slice());
+            if ret < 0 {
+                println!("Error when executing: {}", strerror(ret));
+                exit(-4);
+            }
+            unreachable!();
+        }
+        children.push(pid);
+    }
+    // the readers only see the end of a pipe once every write end is closed
+    for pipe_fd in pipes.iter() {
+        close(pipe_fd[0]);
+        close(pipe_fd[1]);
+    }
+    for pid in children {
+        let mut exit_code: i32 = 0;
+        let exit_pid = waitpid(pid as usize, &mut exit_code);
+        assert_eq!(pid, exit_pid);
+        println!("Shell: Process {} exited with code {}", pid, exit_code);
+    }
+}
 
 #[no_mangle]
 pub fn main() -> i32 {
@@ -27,21 +172,7 @@ pub fn main() -> i32 {
             LF | CR => {
                 println!("");
                 if !line.is_empty() {
-                    line.push('\0');
-                    let pid = fork();
-                    if pid == 0 {
-                        // child process
-                        if exec(line.as_str()) == -1 {
-                            println!("Error when executing!");
-                            return -4;
-                        }
-                        unreachable!();
-                    } else {
-                        let mut exit_code: i32 = 0;
-                        let exit_pid = waitpid(pid as usize, &mut exit_code);
-                        assert_eq!(pid, exit_pid);
-                        println!("Shell: Process {} exited with code {}", pid, exit_code);
-                    }
+                    run(line.as_str());
                     line.clear();
                 }
                 print!(">> ");
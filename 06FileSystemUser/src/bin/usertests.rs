@@ -11,6 +11,12 @@ extern crate user_lib;
 static SUCC_TESTS: &[(&str, &str, &str, &str, i32)] = &[
     ("filetest_simple\0", "\0", "\0", "\0", 0),
     ("cat_filea\0", "\0", "\0", "\0", 0),
+    ("clocktest_simple\0", "\0", "\0", "\0", 0),
+    ("cmdline_args\0", "1\0", "2\0", "3\0", 0),
+    ("cowtest_simple\0", "\0", "\0", "\0", 0),
+    ("dirtest_simple\0", "\0", "\0", "\0", 0),
+    ("efaulttest_simple\0", "\0", "\0", "\0", 0),
+    ("duptest_simple\0", "\0", "\0", "\0", 0),
     ("exit\0", "\0", "\0", "\0", 0),
     ("fantastic_text\0", "\0", "\0", "\0", 0),
     ("forktest_simple\0", "\0", "\0", "\0", 0),
@@ -19,19 +25,32 @@ static SUCC_TESTS: &[(&str, &str, &str, &str, i32)] = &[
     ("forktree\0", "\0", "\0", "\0", 0),
     ("hello_world\0", "\0", "\0", "\0", 0),
     ("huge_write\0", "\0", "\0", "\0", 0),
+    ("lazytest_simple\0", "\0", "\0", "\0", 0),
+    ("linktest_simple\0", "\0", "\0", "\0", 0),
     ("matrix\0", "\0", "\0", "\0", 0),
+    ("mmaptest_simple\0", "\0", "\0", "\0", 0),
+    ("opentest_simple\0", "\0", "\0", "\0", 0),
+    ("pipetest_simple\0", "\0", "\0", "\0", 0),
+    ("prioritytest_simple\0", "\0", "\0", "\0", 0),
+    ("sbrktest_simple\0", "\0", "\0", "\0", 0),
+    ("seektest_simple\0", "\0", "\0", "\0", 0),
+    ("sigtest_simple\0", "\0", "\0", "\0", 0),
     ("sleep_simple\0", "\0", "\0", "\0", 0),
     ("sleep\0", "\0", "\0", "\0", 0),
+    ("swaptest_simple\0", "\0", "\0", "\0", 0),
     ("yield\0", "\0", "\0", "\0", 0),
 ];
 
-static FAIL_TESTS: &[(&str, &str, &str, &str, i32)] = &[("stack_overflow\0", "\0", "\0", "\0", -2)];
+static FAIL_TESTS: &[(&str, &str, &str, &str, i32)] =
+    &[("stack_overflow\0", "\0", "\0", "\0", -11)];
 
 use user_lib::{exec, fork, waitpid};
 
 fn run_tests(tests: &[(&str, &str, &str, &str, i32)]) -> i32 {
     let mut pass_num = 0;
-    let mut arr: [*const u8; 4] = [
+    // argv_0 to argv_3 and a terminating NULL
+    let mut arr: [*const u8; 5] = [
+        core::ptr::null::<u8>(),
         core::ptr::null::<u8>(),
         core::ptr::null::<u8>(),
         core::ptr::null::<u8>(),
@@ -65,7 +84,7 @@ fn run_tests(tests: &[(&str, &str, &str, &str, i32)]) -> i32 {
 
         let pid = fork();
         if pid == 0 {
-            exec(test.0);
+            exec(test.0, &arr[..]);
             panic!("unreachable!");
         } else {
             let mut exit_code: i32 = Default::default();
@@ -0,0 +1,101 @@
+//! Error numbers returned negated by failed syscalls
+
+/// Error number of a failed syscall, the same as those of the kernel
+#[repr(isize)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Errno {
+    EPERM = 1,
+    ENOENT = 2,
+    ESRCH = 3,
+    EINTR = 4,
+    E2BIG = 7,
+    ENOEXEC = 8,
+    EBADF = 9,
+    ECHILD = 10,
+    EAGAIN = 11,
+    ENOMEM = 12,
+    EACCES = 13,
+    EFAULT = 14,
+    EEXIST = 17,
+    ENODEV = 19,
+    ENOTDIR = 20,
+    EISDIR = 21,
+    EINVAL = 22,
+    ETXTBSY = 26,
+    ESPIPE = 29,
+    ENAMETOOLONG = 36,
+    ENOSYS = 38,
+    ENOTEMPTY = 39,
+}
+
+const ALL_ERRNOS: [Errno; 22] = [
+    Errno::EPERM,
+    Errno::ENOENT,
+    Errno::ESRCH,
+    Errno::EINTR,
+    Errno::E2BIG,
+    Errno::ENOEXEC,
+    Errno::EBADF,
+    Errno::ECHILD,
+    Errno::EAGAIN,
+    Errno::ENOMEM,
+    Errno::EACCES,
+    Errno::EFAULT,
+    Errno::EEXIST,
+    Errno::ENODEV,
+    Errno::ENOTDIR,
+    Errno::EISDIR,
+    Errno::EINVAL,
+    Errno::ETXTBSY,
+    Errno::ESPIPE,
+    Errno::ENAMETOOLONG,
+    Errno::ENOSYS,
+    Errno::ENOTEMPTY,
+];
+
+impl Errno {
+    /// Decode the return value of a syscall, `None` if it did not fail
+    pub fn from_ret(ret: isize) -> Option<Self> {
+        if ret >= 0 {
+            return None;
+        }
+        ALL_ERRNOS
+            .iter()
+            .copied()
+            .find(|&errno| errno as isize == -ret)
+    }
+    /// The return value of a syscall failing with `self`
+    pub fn ret(self) -> isize {
+        -(self as isize)
+    }
+}
+
+/// Describe the error of a failed syscall return value, like `strerror` of libc
+pub fn strerror(ret: isize) -> &'static str {
+    match Errno::from_ret(ret) {
+        None if ret >= 0 => "Success",
+        None => "Unknown error",
+        Some(Errno::EPERM) => "Operation not permitted",
+        Some(Errno::ENOENT) => "No such file or directory",
+        Some(Errno::ESRCH) => "No such process",
+        Some(Errno::EINTR) => "Interrupted system call",
+        Some(Errno::E2BIG) => "Argument list too long",
+        Some(Errno::ENOEXEC) => "Exec format error",
+        Some(Errno::EBADF) => "Bad file descriptor",
+        Some(Errno::ECHILD) => "No child processes",
+        Some(Errno::EAGAIN) => "Try again",
+        Some(Errno::ENOMEM) => "Out of memory",
+        Some(Errno::EACCES) => "Permission denied",
+        Some(Errno::EFAULT) => "Bad address",
+        Some(Errno::EEXIST) => "File exists",
+        Some(Errno::ENODEV) => "No such device",
+        Some(Errno::ENOTDIR) => "Not a directory",
+        Some(Errno::EISDIR) => "Is a directory",
+        Some(Errno::EINVAL) => "Invalid argument",
+        Some(Errno::ETXTBSY) => "Text file busy",
+        Some(Errno::ESPIPE) => "Illegal seek",
+        Some(Errno::ENAMETOOLONG) => "File name too long",
+        Some(Errno::ENOSYS) => "Function not implemented",
+        Some(Errno::ENOTEMPTY) => "Directory not empty",
+    }
+}
@@ -5,6 +5,7 @@
 
 #[macro_use]
 pub mod console;
+mod errno;
 mod lang_items;
 mod syscall;
 
@@ -12,65 +13,410 @@ extern crate alloc;
 #[macro_use]
 extern crate bitflags;
 
+use alloc::vec::Vec;
 use buddy_system_allocator::LockedHeap;
+use core::alloc::{GlobalAlloc, Layout};
+use core::ptr::{null_mut, NonNull};
+use core::sync::atomic::{AtomicUsize, Ordering};
 use syscall::*;
 
-const USER_HEAP_SIZE: usize = 32768;
+pub use errno::{strerror, Errno};
 
-static mut HEAP_SPACE: [u8; USER_HEAP_SIZE] = [0; USER_HEAP_SIZE];
+const PAGE_SIZE: usize = 0x1000;
+/// The heap grows by at least this many bytes at a time
+const USER_HEAP_INCREMENT: usize = 32768;
+
+/// A heap that asks the kernel for more memory through `sbrk` when it runs out
+struct SbrkHeap(LockedHeap);
+
+unsafe impl GlobalAlloc for SbrkHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let mut heap = self.0.lock();
+        if let Ok(ptr) = heap.alloc(layout) {
+            return ptr.as_ptr();
+        }
+        // the new memory does not merge with the old one, but twice the
+        // rounded size always holds an aligned block for `layout`
+        let size =
+            (layout.size().max(layout.align()).next_power_of_two() * 2).max(USER_HEAP_INCREMENT);
+        let size = (size + PAGE_SIZE - 1) / PAGE_SIZE * PAGE_SIZE;
+        if size > i32::MAX as usize {
+            return null_mut();
+        }
+        let start = sbrk(size as i32);
+        if start < 0 {
+            return null_mut();
+        }
+        heap.add_to_heap(start as usize, start as usize + size);
+        heap.alloc(layout).map_or(null_mut(), |ptr| ptr.as_ptr())
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        self.0.lock().dealloc(NonNull::new_unchecked(ptr), layout)
+    }
+}
 
 #[global_allocator]
-static HEAP: LockedHeap = LockedHeap::empty();
+static HEAP: SbrkHeap = SbrkHeap(LockedHeap::empty());
 
 #[alloc_error_handler]
 pub fn handle_alloc_error(layout: core::alloc::Layout) -> ! {
     panic!("Heap allocation error, layout = {:?}", layout);
 }
 
+/// The NULL-terminated array of environment variables of the program, as
+/// `NAME=value`. It stays on the user stack for the whole run.
+static ENVP: AtomicUsize = AtomicUsize::new(0);
+
+/// Collect the NULL-terminated array of C strings at `ptr`.
+/// `ptr` is NULL for initproc, which is not started by `exec`.
+fn parse_str_array(ptr: usize) -> Vec<&'static str> {
+    let mut v: Vec<&'static str> = Vec::new();
+    if ptr == 0 {
+        return v;
+    }
+    let mut p = ptr as *const usize;
+    loop {
+        let str_start = unsafe { p.read_volatile() };
+        if str_start == 0 {
+            break;
+        }
+        let len = (0usize..)
+            .find(|i| unsafe { ((str_start + *i) as *const u8).read_volatile() == 0 })
+            .unwrap();
+        v.push(
+            core::str::from_utf8(unsafe {
+                core::slice::from_raw_parts(str_start as *const u8, len)
+            })
+            .unwrap(),
+        );
+        p = unsafe { p.add(1) };
+    }
+    v
+}
+
 #[no_mangle]
 #[link_section = ".text.entry"]
-pub extern "C" fn _start() -> ! {
-    unsafe {
-        HEAP.lock()
-            .init(HEAP_SPACE.as_ptr() as usize, USER_HEAP_SIZE);
-    }
-    exit(main());
+pub extern "C" fn _start(argc: usize, argv: usize, envp: usize) -> ! {
+    let args = parse_str_array(argv);
+    assert_eq!(args.len(), argc);
+    ENVP.store(envp, Ordering::Relaxed);
+    exit(main(argc, args.as_slice()));
 }
 
 #[linkage = "weak"]
 #[no_mangle]
-fn main() -> i32 {
+fn main(_argc: usize, _argv: &[&str]) -> i32 {
     panic!("Cannot find main!");
 }
 
+/// Look up the environment variable `name` given to `exec`.
+pub fn getenv(name: &str) -> Option<&'static str> {
+    parse_str_array(ENVP.load(Ordering::Relaxed))
+        .into_iter()
+        .find_map(|env| {
+            env.strip_prefix(name)
+                .and_then(|rest| rest.strip_prefix('='))
+        })
+}
+
 bitflags! {
     pub struct OpenFlags: u32 {
         const RDONLY = 0;
         const WRONLY = 1 << 0;
         const RDWR = 1 << 1;
+        const EXCL = 1 << 7;
         const CREATE = 1 << 9;
         const TRUNC = 1 << 10;
+        const APPEND = 1 << 11;
+        const DIRECTORY = 1 << 16;
     }
 }
 
+/// Clock of `clock_gettime`: wall-clock time since the Unix epoch
+pub const CLOCK_REALTIME: usize = 0;
+/// Clock of `clock_gettime`: time since boot, which never jumps
+pub const CLOCK_MONOTONIC: usize = 1;
+
+/// A span of time, or a point of time since some epoch
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct TimeSpec {
+    /// seconds
+    pub tv_sec: usize,
+    /// nanoseconds, less than a second
+    pub tv_nsec: usize,
+}
+
+/// The stat of a inode
+#[repr(C)]
+#[derive(Debug)]
+pub struct Stat {
+    /// ID of device containing file
+    pub dev: u64,
+    /// inode number
+    pub ino: u64,
+    /// file type and mode
+    pub mode: StatMode,
+    /// number of hard links
+    pub nlink: u32,
+    /// total size in bytes
+    pub size: u64,
+}
+
+impl Stat {
+    pub fn new() -> Self {
+        Stat {
+            dev: 0,
+            ino: 0,
+            mode: StatMode::NULL,
+            nlink: 0,
+            size: 0,
+        }
+    }
+}
+
+impl Default for Stat {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+bitflags! {
+    pub struct StatMode: u32 {
+        const NULL  = 0;
+        /// pipe
+        const FIFO  = 0o010000;
+        /// character device
+        const CHR   = 0o020000;
+        /// directory
+        const DIR   = 0o040000;
+        /// ordinary regular file
+        const FILE  = 0o100000;
+    }
+}
+
+/// Handler address meaning the default action
+pub const SIG_DFL: usize = 0;
+/// Handler address meaning the signal is ignored
+pub const SIG_IGN: usize = 1;
+
+pub const SIGHUP: i32 = 1;
+pub const SIGINT: i32 = 2;
+pub const SIGQUIT: i32 = 3;
+pub const SIGILL: i32 = 4;
+pub const SIGTRAP: i32 = 5;
+pub const SIGABRT: i32 = 6;
+pub const SIGBUS: i32 = 7;
+pub const SIGFPE: i32 = 8;
+pub const SIGKILL: i32 = 9;
+pub const SIGUSR1: i32 = 10;
+pub const SIGSEGV: i32 = 11;
+pub const SIGUSR2: i32 = 12;
+pub const SIGPIPE: i32 = 13;
+pub const SIGALRM: i32 = 14;
+pub const SIGTERM: i32 = 15;
+pub const SIGSTKFLT: i32 = 16;
+pub const SIGCHLD: i32 = 17;
+pub const SIGCONT: i32 = 18;
+pub const SIGSTOP: i32 = 19;
+pub const SIGTSTP: i32 = 20;
+pub const SIGTTIN: i32 = 21;
+pub const SIGTTOU: i32 = 22;
+pub const SIGURG: i32 = 23;
+pub const SIGXCPU: i32 = 24;
+pub const SIGXFSZ: i32 = 25;
+pub const SIGVTALRM: i32 = 26;
+pub const SIGPROF: i32 = 27;
+pub const SIGWINCH: i32 = 28;
+pub const SIGIO: i32 = 29;
+pub const SIGPWR: i32 = 30;
+pub const SIGSYS: i32 = 31;
+
+bitflags! {
+    /// A set of signals, where signal `n` is bit `n`
+    pub struct SignalFlags: u32 {
+        const SIGHUP = 1 << SIGHUP;
+        const SIGINT = 1 << SIGINT;
+        const SIGQUIT = 1 << SIGQUIT;
+        const SIGILL = 1 << SIGILL;
+        const SIGTRAP = 1 << SIGTRAP;
+        const SIGABRT = 1 << SIGABRT;
+        const SIGBUS = 1 << SIGBUS;
+        const SIGFPE = 1 << SIGFPE;
+        const SIGKILL = 1 << SIGKILL;
+        const SIGUSR1 = 1 << SIGUSR1;
+        const SIGSEGV = 1 << SIGSEGV;
+        const SIGUSR2 = 1 << SIGUSR2;
+        const SIGPIPE = 1 << SIGPIPE;
+        const SIGALRM = 1 << SIGALRM;
+        const SIGTERM = 1 << SIGTERM;
+        const SIGSTKFLT = 1 << SIGSTKFLT;
+        const SIGCHLD = 1 << SIGCHLD;
+        const SIGCONT = 1 << SIGCONT;
+        const SIGSTOP = 1 << SIGSTOP;
+        const SIGTSTP = 1 << SIGTSTP;
+        const SIGTTIN = 1 << SIGTTIN;
+        const SIGTTOU = 1 << SIGTTOU;
+        const SIGURG = 1 << SIGURG;
+        const SIGXCPU = 1 << SIGXCPU;
+        const SIGXFSZ = 1 << SIGXFSZ;
+        const SIGVTALRM = 1 << SIGVTALRM;
+        const SIGPROF = 1 << SIGPROF;
+        const SIGWINCH = 1 << SIGWINCH;
+        const SIGIO = 1 << SIGIO;
+        const SIGPWR = 1 << SIGPWR;
+        const SIGSYS = 1 << SIGSYS;
+    }
+}
+
+/// Action taken on delivery of a signal.
+/// A handler gets the signal number and must end with `sigreturn`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct SignalAction {
+    /// address of the handler, or `SIG_DFL`/`SIG_IGN`
+    pub handler: usize,
+    /// signals blocked while the handler runs
+    pub mask: SignalFlags,
+}
+
+impl Default for SignalAction {
+    fn default() -> Self {
+        Self {
+            handler: SIG_DFL,
+            mask: SignalFlags::empty(),
+        }
+    }
+}
+
+/// `whence` of `lseek`: set the offset to `offset`
+pub const SEEK_SET: usize = 0;
+/// `whence` of `lseek`: set the offset to current offset plus `offset`
+pub const SEEK_CUR: usize = 1;
+/// `whence` of `lseek`: set the offset to file size plus `offset`
+pub const SEEK_END: usize = 2;
+
+/// Pages of a mapping may be read
+pub const PROT_READ: u32 = 1 << 0;
+/// Pages of a mapping may be written
+pub const PROT_WRITE: u32 = 1 << 1;
+/// Pages of a mapping may be executed
+pub const PROT_EXEC: u32 = 1 << 2;
+/// Writes to a mapping go back to the file and are seen by children
+pub const MAP_SHARED: u32 = 1 << 0;
+/// Writes to a mapping are private to the process
+pub const MAP_PRIVATE: u32 = 1 << 1;
+/// Map exactly at `addr` or fail
+pub const MAP_FIXED: u32 = 1 << 4;
+/// Map zeros instead of a file, `fd` is ignored
+pub const MAP_ANONYMOUS: u32 = 1 << 5;
+/// `msync`: start the write-back and return
+pub const MS_ASYNC: u32 = 1;
+/// `msync`: write back before returning
+pub const MS_SYNC: u32 = 4;
+
+/// Resolve relative paths from the current directory
+pub const AT_FDCWD: isize = -100;
+/// Let `unlinkat` remove a directory instead of a file
+pub const AT_REMOVEDIR: u32 = 0x200;
+
+pub fn mkdir(path: &str) -> isize {
+    sys_mkdirat(AT_FDCWD, path, 0)
+}
+pub fn rmdir(path: &str) -> isize {
+    sys_unlinkat(AT_FDCWD, path, AT_REMOVEDIR)
+}
+pub fn link(old_path: &str, new_path: &str) -> isize {
+    sys_linkat(AT_FDCWD, old_path, AT_FDCWD, new_path, 0)
+}
+pub fn unlink(path: &str) -> isize {
+    sys_unlinkat(AT_FDCWD, path, 0)
+}
 pub fn open(path: &str, flags: OpenFlags) -> isize {
     sys_open(path, flags.bits)
 }
 pub fn close(fd: usize) -> isize {
     sys_close(fd)
 }
+pub fn dup(fd: usize) -> isize {
+    sys_dup(fd)
+}
+pub fn dup3(old_fd: usize, new_fd: usize, flags: u32) -> isize {
+    sys_dup3(old_fd, new_fd, flags)
+}
+pub fn pipe(pipe_fd: &mut [usize]) -> isize {
+    sys_pipe(pipe_fd)
+}
 pub fn read(fd: usize, buf: &mut [u8]) -> isize {
     sys_read(fd, buf)
 }
 pub fn write(fd: usize, buf: &[u8]) -> isize {
     sys_write(fd, buf)
 }
+pub fn lseek(fd: usize, offset: isize, whence: usize) -> isize {
+    sys_lseek(fd, offset, whence)
+}
+pub fn pread(fd: usize, buf: &mut [u8], offset: usize) -> isize {
+    sys_pread64(fd, buf, offset)
+}
+pub fn pwrite(fd: usize, buf: &[u8], offset: usize) -> isize {
+    sys_pwrite64(fd, buf, offset)
+}
+pub fn fstat(fd: usize, st: &mut Stat) -> isize {
+    sys_fstat(fd, st)
+}
+/// Map `len` bytes of the file `fd` from `offset`, or zeros with `MAP_ANONYMOUS`.
+/// Return the address of the mapping, or the negated error number.
+pub fn mmap(addr: usize, len: usize, prot: u32, flags: u32, fd: usize, offset: usize) -> isize {
+    sys_mmap(addr, len, prot, flags, fd, offset)
+}
+pub fn munmap(addr: usize, len: usize) -> isize {
+    sys_munmap(addr, len)
+}
+pub fn msync(addr: usize, len: usize, flags: u32) -> isize {
+    sys_msync(addr, len, flags)
+}
+/// Move the program break by `size` bytes and return the old one, or `-ENOMEM`.
+/// Memory returned by `sbrk` must not be shrunk away under the global allocator.
+pub fn sbrk(size: i32) -> isize {
+    sys_sbrk(size)
+}
 pub fn exit(exit_code: i32) -> ! {
     sys_exit(exit_code);
 }
 pub fn yield_() -> isize {
     sys_yield()
 }
+pub fn kill(pid: usize, signum: i32) -> isize {
+    sys_kill(pid, signum)
+}
+pub fn sigaction(
+    signum: i32,
+    action: Option<&SignalAction>,
+    old_action: Option<&mut SignalAction>,
+) -> isize {
+    sys_sigaction(
+        signum,
+        action.map_or(core::ptr::null(), |a| a),
+        old_action.map_or(core::ptr::null_mut(), |a| a),
+    )
+}
+/// Block the signals in `mask` and return the previous mask.
+pub fn sigprocmask(mask: u32) -> isize {
+    sys_sigprocmask(mask)
+}
+pub fn sigreturn() -> isize {
+    sys_sigreturn()
+}
+/// Set the priority of the current process, at least 2, and return it.
+/// It is a no-op unless the kernel is built with the stride or CFS scheduler.
+pub fn set_priority(prio: isize) -> isize {
+    sys_set_priority(prio)
+}
+/// Read the clock `clock_id` into `tp`, return 0 or the negated error number
+pub fn clock_gettime(clock_id: usize, tp: &mut TimeSpec) -> isize {
+    sys_clock_gettime(clock_id, tp)
+}
 pub fn get_time() -> isize {
     sys_get_time()
 }
@@ -80,16 +426,21 @@ pub fn getpid() -> isize {
 pub fn fork() -> isize {
     sys_fork()
 }
-pub fn exec(path: &str) -> isize {
-    sys_exec(path)
+/// Run the program at `path` with the NULL-terminated pointer array `args`.
+pub fn exec(path: &str, args: &[*const u8]) -> isize {
+    sys_exec(path, args, &[core::ptr::null::<u8>()])
+}
+/// `exec` that also passes the NULL-terminated `NAME=value` array `envs`.
+pub fn execve(path: &str, args: &[*const u8], envs: &[*const u8]) -> isize {
+    sys_exec(path, args, envs)
 }
 pub fn wait(exit_code: &mut i32) -> isize {
     loop {
         match sys_waitpid(-1, exit_code as *mut _) {
-            -2 => {
+            ret if ret == Errno::EAGAIN.ret() => {
                 yield_();
             }
-            // -1 or a real pid
+            // an error or a real pid
             exit_pid => return exit_pid,
         }
     }
@@ -98,17 +449,22 @@ pub fn wait(exit_code: &mut i32) -> isize {
 pub fn waitpid(pid: usize, exit_code: &mut i32) -> isize {
     loop {
         match sys_waitpid(pid as isize, exit_code as *mut _) {
-            -2 => {
+            ret if ret == Errno::EAGAIN.ret() => {
                 yield_();
             }
-            // -1 or a real pid
+            // an error or a real pid
             exit_pid => return exit_pid,
         }
     }
 }
+/// Block for `req`, return 0 or the negated error number
+pub fn nanosleep(req: &TimeSpec) -> isize {
+    sys_nanosleep(req, core::ptr::null_mut())
+}
 pub fn sleep(period_ms: usize) {
-    let start = sys_get_time();
-    while sys_get_time() < start + period_ms as isize {
-        sys_yield();
-    }
+    let req = TimeSpec {
+        tv_sec: period_ms / 1000,
+        tv_nsec: period_ms % 1000 * 1_000_000,
+    };
+    nanosleep(&req);
 }
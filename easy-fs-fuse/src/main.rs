@@ -55,7 +55,8 @@ fn easy_fs_pack() -> std::io::Result<()> {
             .write(true)
             .create(true)
             .open(format!("{}{}", target_path, "fs.img"))?;
-        f.set_len(16 * 2048 * 512).unwrap();
+        // followed by 64MiB of swap for the kernel
+        f.set_len((16 * 2048 + 16384 * 8) * 512).unwrap();
         f
     })));
     // 16MiB, at most 4095 files
@@ -88,20 +89,33 @@ fn easy_fs_pack() -> std::io::Result<()> {
     Ok(())
 }
 
-#[test]
-fn efs_test() -> std::io::Result<()> {
+/// The block cache is global and keyed by block id only,
+/// so tests sharing it must run one at a time on the same image.
+#[cfg(test)]
+static EFS_TEST_LOCK: Mutex<()> = Mutex::new(());
+
+/// Format a new file system on `target/fs.img` and return its root directory
+#[cfg(test)]
+fn fresh_efs() -> std::io::Result<Arc<easy_fs::Inode>> {
     let block_file = Arc::new(BlockFile(Mutex::new({
         let f = OpenOptions::new()
             .read(true)
             .write(true)
             .create(true)
+            .truncate(false)
             .open("target/fs.img")?;
         f.set_len(8192 * 512).unwrap();
         f
     })));
     EasyFileSystem::create(block_file.clone(), 4096, 1);
-    let efs = EasyFileSystem::open(block_file.clone());
-    let root_inode = EasyFileSystem::root_inode(&efs);
+    let efs = EasyFileSystem::open(block_file);
+    Ok(Arc::new(EasyFileSystem::root_inode(&efs)))
+}
+
+#[test]
+fn efs_test() -> std::io::Result<()> {
+    let _guard = EFS_TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let root_inode = fresh_efs()?;
     root_inode.create("filea");
     root_inode.create("fileb");
     for name in root_inode.ls() {
@@ -150,3 +164,144 @@ fn efs_test() -> std::io::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn efs_dir_test() -> std::io::Result<()> {
+    let _guard = EFS_TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let root_inode = fresh_efs()?;
+    assert_eq!(root_inode.ls(), [".", ".."]);
+
+    let dira = root_inode.create_dir("dira").unwrap();
+    assert!(dira.is_dir());
+    assert!(root_inode.create_dir("dira").is_none());
+    assert_eq!(dira.ls(), [".", ".."]);
+    let dirb = dira.create_dir("dirb").unwrap();
+    let filea = dirb.create("filea").unwrap();
+    assert!(!filea.is_dir());
+    // walk back up through ".."
+    let up = dirb.find("..").unwrap().find("..").unwrap();
+    assert_eq!(up.ls(), root_inode.ls());
+    assert!(up
+        .find("dira")
+        .unwrap()
+        .find("dirb")
+        .unwrap()
+        .find("filea")
+        .is_some());
+
+    // refuse non-empty directories, files and dot entries
+    assert!(!root_inode.rmdir("dira"));
+    assert!(!dirb.rmdir("filea"));
+    assert!(!dira.rmdir("."));
+    assert!(!dira.rmdir(".."));
+    assert!(!dira.rmdir("dirc"));
+
+    // the freed inode and dirent slot are reused
+    let dirc = dira.create_dir("dirc").unwrap();
+    assert!(dira.rmdir("dirc"));
+    assert!(dira.find("dirc").is_none());
+    assert_eq!(dira.ls(), [".", "..", "dirb"]);
+    let dird = dira.create_dir("dird").unwrap();
+    assert_eq!(dira.ls(), [".", "..", "dirb", "dird"]);
+    assert_eq!(dird.find(".").unwrap().ls(), dirc.ls());
+    Ok(())
+}
+
+#[test]
+fn efs_unlink_test() -> std::io::Result<()> {
+    let _guard = EFS_TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let root_inode = fresh_efs()?;
+    let dira = root_inode.create_dir("dira").unwrap();
+    assert!(!root_inode.unlink("dira"));
+    assert!(!root_inode.unlink("filea"));
+
+    // the data area only holds one such file at a time
+    let data = [b'x'; 3000 * BLOCK_SZ];
+    for _ in 0..3 {
+        let filea = dira.create("filea").unwrap();
+        assert_eq!(filea.write_at(0, &data), data.len());
+        assert!(dira.unlink("filea"));
+        assert!(dira.find("filea").is_none());
+    }
+    assert_eq!(dira.ls(), [".", ".."]);
+
+    // writing past the end over reused blocks leaves a hole of zeros
+    let filea = dira.create("filea").unwrap();
+    assert_eq!(filea.write_at(100 * BLOCK_SZ, b"!"), 1);
+    assert_eq!(filea.size() as usize, 100 * BLOCK_SZ + 1);
+    let mut hole = [b'x'; 100 * BLOCK_SZ];
+    assert_eq!(filea.read_at(0, &mut hole), hole.len());
+    assert!(hole.iter().all(|&b| b == 0));
+    assert!(dira.unlink("filea"));
+
+    // an unlinked file stays readable and writable until it is dropped
+    let filea = dira.create("filea").unwrap();
+    let inode_id = filea.inode_id();
+    assert_eq!(filea.write_at(0, b"still here"), 10);
+    assert!(dira.unlink("filea"));
+    assert!(dira.find("filea").is_none());
+    assert_eq!(filea.nlink(), 0);
+    // it can not be linked back
+    assert!(!dira.link(&filea, "filea"));
+    assert!(dira.find("filea").is_none());
+    let fileb = dira.create("fileb").unwrap();
+    assert_ne!(fileb.inode_id(), inode_id);
+    assert_eq!(fileb.write_at(0, &data[..2000 * BLOCK_SZ]), 2000 * BLOCK_SZ);
+    let mut buffer = [0u8; 10];
+    assert_eq!(filea.read_at(0, &mut buffer), 10);
+    assert_eq!(&buffer, b"still here");
+    assert_eq!(filea.write_at(10, b"!"), 1);
+    assert_eq!(filea.size(), 11);
+    // released with its last reference, so that its inode is reused
+    drop(filea);
+    assert!(dira.unlink("fileb"));
+    drop(fileb);
+    assert_eq!(dira.create("filec").unwrap().inode_id(), inode_id);
+    assert!(dira.unlink("filec"));
+
+    // more files than there are inodes
+    for _ in 0..5000 {
+        root_inode.create("fileb").unwrap();
+        assert!(root_inode.unlink("fileb"));
+    }
+    assert!(root_inode.rmdir("dira"));
+    assert_eq!(root_inode.ls(), [".", ".."]);
+    Ok(())
+}
+
+#[test]
+fn efs_link_test() -> std::io::Result<()> {
+    let _guard = EFS_TEST_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let root_inode = fresh_efs()?;
+    assert_eq!(root_inode.nlink(), 2);
+    let dira = root_inode.create_dir("dira").unwrap();
+    assert_eq!(root_inode.nlink(), 3);
+    assert_eq!(root_inode.inode_id(), 0);
+    assert_eq!(dira.find("..").unwrap().inode_id(), 0);
+    assert_eq!(dira.find(".").unwrap().inode_id(), dira.inode_id());
+    assert_eq!(dira.nlink(), 2);
+
+    let filea = root_inode.create("filea").unwrap();
+    assert_eq!(filea.nlink(), 1);
+    let greet_str = "Hello, world!";
+    filea.write_at(0, greet_str.as_bytes());
+    assert_eq!(filea.size() as usize, greet_str.len());
+    assert!(dira.link(&filea, "fileb"));
+    assert!(!dira.link(&filea, "fileb"));
+    assert!(!root_inode.link(&dira, "dirb"));
+    assert_eq!(filea.nlink(), 2);
+
+    // data is kept until the last link is removed
+    assert!(root_inode.unlink("filea"));
+    let fileb = dira.find("fileb").unwrap();
+    assert_eq!(fileb.inode_id(), filea.inode_id());
+    assert_eq!(fileb.nlink(), 1);
+    let mut buffer = [0u8; 233];
+    let len = fileb.read_at(0, &mut buffer);
+    assert_eq!(greet_str, core::str::from_utf8(&buffer[..len]).unwrap());
+    assert!(dira.unlink("fileb"));
+
+    assert!(root_inode.rmdir("dira"));
+    assert_eq!(root_inode.nlink(), 2);
+    Ok(())
+}
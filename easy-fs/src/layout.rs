@@ -5,8 +5,9 @@ use core::fmt::{Debug, Formatter, Result};
 
 /// Magic number for sanity check
 const EFS_MAGIC: u32 = 0x3b800001;
-/// The max number of direct inodes
-const INODE_DIRECT_COUNT: usize = 28;
+/// The max number of direct inodes, leaving room for the link count
+/// so that a disk inode still takes 128 bytes
+const INODE_DIRECT_COUNT: usize = 27;
 /// The max length of inode name
 const NAME_LENGTH_LIMIT: usize = 27;
 /// The max number of indirect1 inodes
@@ -85,17 +86,25 @@ pub struct DiskInode {
     pub direct: [u32; INODE_DIRECT_COUNT],
     pub indirect1: u32,
     pub indirect2: u32,
+    /// Number of dirents pointing to this inode
+    pub nlink: u32,
     type_: DiskInodeType,
 }
 
 impl DiskInode {
     /// Initialize a disk inode, as well as all direct inodes under it
     /// indirect1 and indirect2 block are allocated only when they are needed
+    /// a file starts with one link from its parent, and a directory also links to itself by "."
     pub fn initialize(&mut self, type_: DiskInodeType) {
         self.size = 0;
         self.direct.iter_mut().for_each(|v| *v = 0);
         self.indirect1 = 0;
         self.indirect2 = 0;
+        self.nlink = if type_ == DiskInodeType::Directory {
+            2
+        } else {
+            1
+        };
         self.type_ = type_;
     }
     /// Whether this inode is a directory
@@ -431,4 +440,8 @@ impl DirEntry {
     pub fn inode_number(&self) -> u32 {
         self.inode_number
     }
+    /// Whether the entry is a free slot left by a removed file
+    pub fn is_empty(&self) -> bool {
+        self.name[0] == 0
+    }
 }
@@ -15,13 +15,16 @@ pub struct Inode {
 }
 
 impl Inode {
-    /// Create a vfs inode
+    /// Create a vfs inode of `inode_id` with `efs`, the locked `fs`.
+    /// The inode is not released before the vfs inode is dropped.
     pub fn new(
-        block_id: u32,
-        block_offset: usize,
+        inode_id: u32,
+        efs: &mut EasyFileSystem,
         fs: Arc<Mutex<EasyFileSystem>>,
         block_device: Arc<dyn BlockDevice>,
     ) -> Self {
+        let (block_id, block_offset) = efs.get_disk_inode_pos(inode_id);
+        *efs.open_inodes.entry(inode_id).or_insert(0) += 1;
         Self {
             block_id: block_id as usize,
             block_offset,
@@ -52,7 +55,7 @@ impl Inode {
                 disk_inode.read_at(DIRENT_SZ * i, dirent.as_bytes_mut(), &self.block_device,),
                 DIRENT_SZ,
             );
-            if dirent.name() == name {
+            if !dirent.is_empty() && dirent.name() == name {
                 return Some(dirent.inode_number() as u32);
             }
         }
@@ -60,18 +63,14 @@ impl Inode {
     }
     /// Find inode under current inode by name
     pub fn find(&self, name: &str) -> Option<Arc<Inode>> {
-        let fs = self.fs.lock();
-        self.read_disk_inode(|disk_inode| {
-            self.find_inode_id(name, disk_inode).map(|inode_id| {
-                let (block_id, block_offset) = fs.get_disk_inode_pos(inode_id);
-                Arc::new(Self::new(
-                    block_id,
-                    block_offset,
-                    self.fs.clone(),
-                    self.block_device.clone(),
-                ))
-            })
-        })
+        let mut fs = self.fs.lock();
+        let inode_id = self.read_disk_inode(|disk_inode| self.find_inode_id(name, disk_inode))?;
+        Some(Arc::new(Self::new(
+            inode_id,
+            &mut fs,
+            self.fs.clone(),
+            self.block_device.clone(),
+        )))
     }
     /// Increase the size of a disk inode
     fn increase_size(
@@ -90,8 +89,60 @@ impl Inode {
         }
         disk_inode.increase_size(new_size, v, &self.block_device);
     }
-    /// Create inode under current inode by name
-    pub fn create(&self, name: &str) -> Option<Arc<Inode>> {
+    /// Write a dirent into the first free slot of a directory, or append it
+    fn insert_dirent(
+        &self,
+        dirent: &DirEntry,
+        dir_inode: &mut DiskInode,
+        fs: &mut MutexGuard<EasyFileSystem>,
+    ) {
+        let file_count = (dir_inode.size as usize) / DIRENT_SZ;
+        let mut old = DirEntry::empty();
+        let slot = (0..file_count)
+            .find(|i| {
+                dir_inode.read_at(i * DIRENT_SZ, old.as_bytes_mut(), &self.block_device);
+                old.is_empty()
+            })
+            .unwrap_or(file_count);
+        if slot == file_count {
+            // increase size
+            self.increase_size(((file_count + 1) * DIRENT_SZ) as u32, dir_inode, fs);
+        }
+        dir_inode.write_at(slot * DIRENT_SZ, dirent.as_bytes(), &self.block_device);
+    }
+    /// Clear a dirent by name and return the inode id it pointed to
+    fn remove_dirent(&self, name: &str, dir_inode: &mut DiskInode) -> Option<u32> {
+        let file_count = (dir_inode.size as usize) / DIRENT_SZ;
+        let mut dirent = DirEntry::empty();
+        for i in 0..file_count {
+            dir_inode.read_at(i * DIRENT_SZ, dirent.as_bytes_mut(), &self.block_device);
+            if !dirent.is_empty() && dirent.name() == name {
+                let inode_id = dirent.inode_number();
+                dir_inode.write_at(
+                    i * DIRENT_SZ,
+                    DirEntry::empty().as_bytes(),
+                    &self.block_device,
+                );
+                return Some(inode_id);
+            }
+        }
+        None
+    }
+    /// Write "." and ".." into an empty directory
+    fn init_dir_entries(&self, inode_id: u32, parent_id: u32, fs: &mut MutexGuard<EasyFileSystem>) {
+        self.modify_disk_inode(|dir_inode| {
+            self.insert_dirent(&DirEntry::new(".", inode_id), dir_inode, fs);
+            self.insert_dirent(&DirEntry::new("..", parent_id), dir_inode, fs);
+        });
+    }
+    /// Write "." and ".." into current inode, which must be an empty directory
+    pub(crate) fn init_dir(&self, parent_id: u32) {
+        let mut fs = self.fs.lock();
+        let inode_id = fs.get_inode_id(self.block_id as u32, self.block_offset);
+        self.init_dir_entries(inode_id, parent_id, &mut fs);
+    }
+    /// Create inode of given type under current inode by name
+    fn create_inode(&self, name: &str, type_: DiskInodeType) -> Option<Arc<Inode>> {
         let mut fs = self.fs.lock();
         let op = |root_inode: &DiskInode| {
             // assert it is a directory
@@ -102,6 +153,7 @@ impl Inode {
         if self.read_disk_inode(op).is_some() {
             return None;
         }
+        let is_dir = type_ == DiskInodeType::Directory;
         // create a new file
         // alloc a inode with an indirect block
         let new_inode_id = fs.alloc_inode();
@@ -110,50 +162,168 @@ impl Inode {
         get_block_cache(new_inode_block_id as usize, Arc::clone(&self.block_device))
             .lock()
             .modify(new_inode_block_offset, |new_inode: &mut DiskInode| {
-                new_inode.initialize(DiskInodeType::File);
+                new_inode.initialize(type_);
             });
         self.modify_disk_inode(|root_inode| {
-            // append file in the dirent
-            let file_count = (root_inode.size as usize) / DIRENT_SZ;
-            let new_size = (file_count + 1) * DIRENT_SZ;
-            // increase size
-            self.increase_size(new_size as u32, root_inode, &mut fs);
             // write dirent
             let dirent = DirEntry::new(name, new_inode_id);
-            root_inode.write_at(
-                file_count * DIRENT_SZ,
-                dirent.as_bytes(),
-                &self.block_device,
-            );
+            self.insert_dirent(&dirent, root_inode, &mut fs);
+            // ".." of a new directory links back to its parent
+            if is_dir {
+                root_inode.nlink += 1;
+            }
         });
 
-        let (block_id, block_offset) = fs.get_disk_inode_pos(new_inode_id);
+        let inode = Arc::new(Self::new(
+            new_inode_id,
+            &mut fs,
+            self.fs.clone(),
+            self.block_device.clone(),
+        ));
+        if is_dir {
+            let parent_id = fs.get_inode_id(self.block_id as u32, self.block_offset);
+            inode.init_dir_entries(new_inode_id, parent_id, &mut fs);
+        }
         block_cache_sync_all();
         // return inode
-        Some(Arc::new(Self::new(
-            block_id,
-            block_offset,
+        Some(inode)
+        // release efs lock automatically by compiler
+    }
+    /// Create a regular file under current inode by name
+    pub fn create(&self, name: &str) -> Option<Arc<Inode>> {
+        self.create_inode(name, DiskInodeType::File)
+    }
+    /// Create a directory containing "." and ".." under current inode by name
+    pub fn create_dir(&self, name: &str) -> Option<Arc<Inode>> {
+        self.create_inode(name, DiskInodeType::Directory)
+    }
+    /// Find inode under current inode by name, along with its inode id
+    /// The caller must unlock `fs` before dropping the child, see `Drop for Inode`.
+    fn find_child(&self, name: &str, fs: &mut MutexGuard<EasyFileSystem>) -> Option<Inode> {
+        let inode_id = self.read_disk_inode(|disk_inode| self.find_inode_id(name, disk_inode))?;
+        Some(Self::new(
+            inode_id,
+            fs,
             self.fs.clone(),
             self.block_device.clone(),
-        )))
-        // release efs lock automatically by compiler
+        ))
     }
-    /// List inodes under current inode
-    pub fn ls(&self) -> Vec<String> {
+    /// Remove an empty directory under current inode by name.
+    /// Return false if it does not exist, is not a directory or is not empty.
+    pub fn rmdir(&self, name: &str) -> bool {
+        if name == "." || name == ".." {
+            return false;
+        }
+        let mut fs = self.fs.lock();
+        let dir = match self.find_child(name, &mut fs) {
+            Some(child) => child,
+            None => return false,
+        };
+        let removable = dir.read_disk_inode(|disk_inode| {
+            disk_inode.is_dir()
+                && dir
+                    .dirent_names(disk_inode)
+                    .iter()
+                    .all(|name| name == "." || name == "..")
+        });
+        if removable {
+            self.modify_disk_inode(|disk_inode| {
+                self.remove_dirent(name, disk_inode);
+                disk_inode.nlink -= 1;
+            });
+            // neither its dirent nor its "." links to it any more
+            dir.modify_disk_inode(|disk_inode| disk_inode.nlink = 0);
+            block_cache_sync_all();
+        }
+        // the directory is released when its last vfs inode is dropped
+        drop(fs);
+        removable
+    }
+    /// Create a hard link `new` under current inode to the file `old`.
+    /// Return false if `old` is a directory, has no link left, as it is
+    /// released once its last vfs inode is dropped, or `new` already exists.
+    pub fn link(&self, old: &Inode, new: &str) -> bool {
+        assert!(Arc::ptr_eq(&self.fs, &old.fs));
+        let mut fs = self.fs.lock();
+        if old.read_disk_inode(|disk_inode| disk_inode.is_dir() || disk_inode.nlink == 0)
+            || self
+                .read_disk_inode(|disk_inode| self.find_inode_id(new, disk_inode))
+                .is_some()
+        {
+            return false;
+        }
+        let inode_id = fs.get_inode_id(old.block_id as u32, old.block_offset);
+        self.modify_disk_inode(|dir_inode| {
+            self.insert_dirent(&DirEntry::new(new, inode_id), dir_inode, &mut fs);
+        });
+        old.modify_disk_inode(|disk_inode| disk_inode.nlink += 1);
+        block_cache_sync_all();
+        true
+    }
+    /// Remove a file under current inode by name. Its data blocks and inode
+    /// are freed once the last link to it is gone and no vfs inode of it is
+    /// left, so that a file stays readable while it is open, as in Unix.
+    /// Return false if it does not exist or is a directory.
+    pub fn unlink(&self, name: &str) -> bool {
+        let mut fs = self.fs.lock();
+        let file = match self.find_child(name, &mut fs) {
+            Some(child) => child,
+            None => return false,
+        };
+        let unlinked = !file.read_disk_inode(|disk_inode| disk_inode.is_dir());
+        if unlinked {
+            self.modify_disk_inode(|disk_inode| self.remove_dirent(name, disk_inode));
+            file.modify_disk_inode(|disk_inode| disk_inode.nlink -= 1);
+            block_cache_sync_all();
+        }
+        // the file is released when its last vfs inode is dropped
+        drop(fs);
+        unlinked
+    }
+    /// Free the data blocks of current inode and return it to the inode bitmap
+    fn release(&self, inode_id: u32, fs: &mut MutexGuard<EasyFileSystem>) {
+        self.modify_disk_inode(|disk_inode| self.dealloc_data_blocks(disk_inode, fs));
+        fs.dealloc_inode(inode_id);
+    }
+    /// Inode number of current inode
+    pub fn inode_id(&self) -> u32 {
+        let fs = self.fs.lock();
+        fs.get_inode_id(self.block_id as u32, self.block_offset)
+    }
+    /// Size of current inode in bytes
+    pub fn size(&self) -> u32 {
         let _fs = self.fs.lock();
-        self.read_disk_inode(|disk_inode| {
-            let file_count = (disk_inode.size as usize) / DIRENT_SZ;
-            let mut v: Vec<String> = Vec::new();
-            for i in 0..file_count {
-                let mut dirent = DirEntry::empty();
-                assert_eq!(
-                    disk_inode.read_at(i * DIRENT_SZ, dirent.as_bytes_mut(), &self.block_device,),
-                    DIRENT_SZ,
-                );
+        self.read_disk_inode(|disk_inode| disk_inode.size)
+    }
+    /// Number of hard links to current inode
+    pub fn nlink(&self) -> u32 {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| disk_inode.nlink)
+    }
+    /// Whether current inode is a directory
+    pub fn is_dir(&self) -> bool {
+        self.read_disk_inode(|disk_inode| disk_inode.is_dir())
+    }
+    /// Names of all the dirents in use under a directory disk inode
+    fn dirent_names(&self, disk_inode: &DiskInode) -> Vec<String> {
+        let file_count = (disk_inode.size as usize) / DIRENT_SZ;
+        let mut v: Vec<String> = Vec::new();
+        for i in 0..file_count {
+            let mut dirent = DirEntry::empty();
+            assert_eq!(
+                disk_inode.read_at(i * DIRENT_SZ, dirent.as_bytes_mut(), &self.block_device,),
+                DIRENT_SZ,
+            );
+            if !dirent.is_empty() {
                 v.push(String::from(dirent.name()));
             }
-            v
-        })
+        }
+        v
+    }
+    /// List inodes under current inode
+    pub fn ls(&self) -> Vec<String> {
+        let _fs = self.fs.lock();
+        self.read_disk_inode(|disk_inode| self.dirent_names(disk_inode))
     }
     /// Read data from current inode
     pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
@@ -170,17 +340,38 @@ impl Inode {
         block_cache_sync_all();
         size
     }
+    /// Free all the data blocks of a disk inode
+    fn dealloc_data_blocks(&self, disk_inode: &mut DiskInode, fs: &mut MutexGuard<EasyFileSystem>) {
+        let size = disk_inode.size;
+        let data_blocks_dealloc = disk_inode.clear_size(&self.block_device);
+        assert!(data_blocks_dealloc.len() == DiskInode::total_blocks(size) as usize);
+        for data_block in data_blocks_dealloc.into_iter() {
+            fs.dealloc_data(data_block);
+        }
+    }
     /// Clear the data in current inode
     pub fn clear(&self) {
         let mut fs = self.fs.lock();
-        self.modify_disk_inode(|disk_inode| {
-            let size = disk_inode.size;
-            let data_blocks_dealloc = disk_inode.clear_size(&self.block_device);
-            assert!(data_blocks_dealloc.len() == DiskInode::total_blocks(size) as usize);
-            for data_block in data_blocks_dealloc.into_iter() {
-                fs.dealloc_data(data_block);
-            }
-        });
+        self.modify_disk_inode(|disk_inode| self.dealloc_data_blocks(disk_inode, &mut fs));
         block_cache_sync_all();
     }
 }
+
+/// Release an inode with no links left once its last vfs inode is gone.
+/// This takes the fs lock, so a vfs inode must not be dropped while holding it.
+impl Drop for Inode {
+    fn drop(&mut self) {
+        let mut fs = self.fs.lock();
+        let inode_id = fs.get_inode_id(self.block_id as u32, self.block_offset);
+        let count = fs.open_inodes.get_mut(&inode_id).unwrap();
+        *count -= 1;
+        if *count > 0 {
+            return;
+        }
+        fs.open_inodes.remove(&inode_id);
+        if self.read_disk_inode(|disk_inode| disk_inode.nlink) == 0 {
+            self.release(inode_id, &mut fs);
+            block_cache_sync_all();
+        }
+    }
+}